@@ -11,6 +11,26 @@ pub struct CreateRelease {
     pub body: String,
     pub draft: bool,
     pub prerelease: bool,
+    /// Whether this release should be marked as the latest release: `true`,
+    /// `false` or `legacy`. GitHub defaults to `true` when this is left out.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make_latest: Option<String>,
+}
+
+/// The inputs to https://docs.github.com/en/rest/releases/releases#update-a-release,
+/// fields that are left out aren't changed.
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateRelease {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draft: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prerelease: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub make_latest: Option<String>,
 }
 
 /// Release is an individual release of a GitHub repo.
@@ -76,6 +96,17 @@ pub struct Author {
     pub site_admin: bool,
 }
 
+/// A commit in a GitHub repo, only the parts palisade needs of
+/// https://docs.github.com/en/rest/commits/commits#get-a-commit
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Commit {
+    pub sha: String,
+    pub html_url: String,
+    /// The GitHub account of the commit author, if their email belongs to
+    /// one.
+    pub author: Option<Author>,
+}
+
 /// Inputs to create a GitHub repository.
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RepoCreate {
@@ -202,6 +233,78 @@ impl Client {
         }
     }
 
+    /// Gets the release for a tag following the schema here:
+    /// https://docs.github.com/en/rest/releases/releases#get-a-release-by-tag-name
+    /// Returns `None` if there is no release for the tag.
+    pub async fn release_by_tag(
+        &self,
+        owner: String,
+        repo: String,
+        tag: String,
+    ) -> Result<Option<Release>> {
+        // tags such as `release/1.0.0` have to be one path segment, so
+        // let the URL encode the slashes
+        let mut url = reqwest::Url::parse(&format!(
+            "{}repos/{}/{}/releases/tags/",
+            self.base_url, owner, repo
+        ))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("{} can't be used as a base URL", self.base_url))?
+            .pop_if_empty()
+            .push(&tag);
+        let resp = self.cli.get(url).send().await?;
+
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let result: Release = resp.error_for_status()?.json().await?;
+
+        Ok(Some(result))
+    }
+
+    /// Changes an existing release following the schema here:
+    /// https://docs.github.com/en/rest/releases/releases#update-a-release
+    pub async fn update_release(
+        &self,
+        owner: String,
+        repo: String,
+        id: i64,
+        ur: UpdateRelease,
+    ) -> Result<Release> {
+        let result: Release = self
+            .cli
+            .patch(&format!(
+                "{}repos/{}/{}/releases/{}",
+                self.base_url, owner, repo, id
+            ))
+            .json(&ur)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(result)
+    }
+
+    /// Gets a commit following the schema here:
+    /// https://docs.github.com/en/rest/commits/commits#get-a-commit
+    pub async fn commit(&self, owner: String, repo: String, sha: String) -> Result<Commit> {
+        let result: Commit = self
+            .cli
+            .get(&format!(
+                "{}repos/{}/{}/commits/{}",
+                self.base_url, owner, repo, sha
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(result)
+    }
+
     /// Gets the most recent release for a GitHub repo following the schema here:
     /// https://developer.github.com/v3/repos/releases/#get-the-latest-release
     pub async fn newest_release(&self, owner: String, repo: String) -> Result<Release> {
@@ -282,6 +385,7 @@ mod tests {
                 body: "yeah this is a test".into(),
                 draft: false,
                 prerelease: false,
+                make_latest: None,
             },
         )
         .await
@@ -306,4 +410,100 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[tokio::test]
+    async fn release_by_tag() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/create_release.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/repos/yolo/swag/releases/tags/v1.0.0",
+            ))
+            .respond_with(json_encoded(data.clone())),
+        );
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/repos/yolo/swag/releases/tags/v2.0.0",
+            ))
+            .respond_with(status_code(404)),
+        );
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/repos/yolo/swag/releases/tags/release%2F1.0.0",
+            ))
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli = Client::with_url("testswag420".into(), format!("{}", server.url("/"))).unwrap();
+        let release = cli
+            .release_by_tag("yolo".into(), "swag".into(), "v1.0.0".into())
+            .await
+            .unwrap();
+        assert!(release.is_some());
+        let release = cli
+            .release_by_tag("yolo".into(), "swag".into(), "release/1.0.0".into())
+            .await
+            .unwrap();
+        assert!(release.is_some());
+        let release = cli
+            .release_by_tag("yolo".into(), "swag".into(), "v2.0.0".into())
+            .await
+            .unwrap();
+        assert_eq!(release, None);
+    }
+
+    #[tokio::test]
+    async fn update_release() {
+        let data: serde_json::Value =
+            serde_json::from_slice(include_bytes!("../testdata/create_release.json")).unwrap();
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(all_of![
+                request::method_path("PATCH", "/repos/yolo/swag/releases/1"),
+                request::body(json_decoded(eq(serde_json::json!({ "prerelease": true })))),
+            ])
+            .respond_with(json_encoded(data)),
+        );
+
+        let cli = Client::with_url("testswag420".into(), format!("{}", server.url("/"))).unwrap();
+        cli.update_release(
+            "yolo".into(),
+            "swag".into(),
+            1,
+            UpdateRelease {
+                prerelease: Some(true),
+                ..UpdateRelease::default()
+            },
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn commit() {
+        let server = Server::run();
+        server.expect(
+            Expectation::matching(request::method_path(
+                "GET",
+                "/repos/yolo/swag/commits/abc1234",
+            ))
+            .respond_with(json_encoded(serde_json::json!({
+                "sha": "abc1234",
+                "html_url": "https://github.com/yolo/swag/commit/abc1234",
+                "author": null,
+            }))),
+        );
+
+        let cli = Client::with_url("testswag420".into(), format!("{}", server.url("/"))).unwrap();
+        let commit = cli
+            .commit("yolo".into(), "swag".into(), "abc1234".into())
+            .await
+            .unwrap();
+        assert_eq!(commit.sha, "abc1234");
+        assert_eq!(commit.author, None);
+    }
 }
@@ -0,0 +1,322 @@
+use super::model::{link_definition, ParseOptions};
+use anyhow::{anyhow, Result};
+use comrak::nodes::NodeValue;
+use comrak::{parse_document, Arena};
+use std::{ops::Range, str::FromStr};
+
+/// Links ticket keys with a prefix such as `WAT-2392` to an issue tracker.
+/// Written as `PREFIX=URL`, where `{key}` in the URL is replaced with the
+/// whole key, such as `WAT=https://example.atlassian.net/browse/{key}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TicketLink {
+    pub prefix: String,
+    pub url: String,
+}
+
+impl FromStr for TicketLink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('=') {
+            Some((prefix, url)) if !prefix.is_empty() && url.contains("{key}") => Ok(TicketLink {
+                prefix: prefix.trim_end_matches('-').into(),
+                url: url.into(),
+            }),
+            _ => Err(anyhow!(
+                "invalid ticket link {:?}, expected PREFIX=URL with {{key}} in the URL, such as WAT=https://example.atlassian.net/browse/{{key}}",
+                s
+            )),
+        }
+    }
+}
+
+/// Turns references in release notes into links: `#123` and `GH-123` to
+/// issues and pull requests, commit hashes to commits and ticket keys to
+/// their tracker. Code blocks, code spans, existing links, URLs and HTML are
+/// left alone.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Autolinker {
+    /// The repo on GitHub, such as `https://github.com/yolo/swag`. Issues,
+    /// pull requests and commits are only linked if this is set.
+    pub repo_url: Option<String>,
+    pub tickets: Vec<TicketLink>,
+}
+
+impl Autolinker {
+    /// Links the references in `notes`, parsed the same way as the changelog
+    /// with `opts`. `is_commit` says if a word that looks like a commit hash
+    /// really is one, so that other hex strings aren't linked.
+    pub fn link(
+        &self,
+        notes: &str,
+        opts: &ParseOptions,
+        is_commit: &dyn Fn(&str) -> bool,
+    ) -> String {
+        if self.repo_url.is_none() && self.tickets.is_empty() {
+            return notes.to_string();
+        }
+
+        let indented_code = indented_code(notes, opts);
+        let mut result = String::with_capacity(notes.len());
+        let mut fence: Option<&str> = None;
+        for (n, line) in notes.split_inclusive('\n').enumerate() {
+            if indented_code.iter().any(|lines| lines.contains(&n)) {
+                result.push_str(line);
+                continue;
+            }
+            let trimmed = line.trim_start();
+            let marker = ["```", "~~~"].iter().find(|m| trimmed.starts_with(*m));
+            match (fence, marker) {
+                (Some(open), Some(close)) if open == *close => fence = None,
+                (None, Some(open)) => fence = Some(open),
+                (None, None) if link_definition(line.trim_end()).is_none() => {
+                    self.link_line(line, is_commit, &mut result);
+                    continue;
+                }
+                _ => {}
+            }
+            result.push_str(line);
+        }
+
+        result
+    }
+
+    fn link_line(&self, line: &str, is_commit: &dyn Fn(&str) -> bool, result: &mut String) {
+        let mut i = 0;
+        let mut prev: Option<char> = None;
+
+        while let Some(c) = line[i..].chars().next() {
+            let rest = &line[i..];
+            let skip = match c {
+                '`' => {
+                    let ticks = &rest[..rest.len() - rest.trim_start_matches('`').len()];
+                    Some(
+                        rest[ticks.len()..]
+                            .find(ticks)
+                            .map_or(ticks.len(), |end| end + 2 * ticks.len()),
+                    )
+                }
+                '[' => bracket_end(rest),
+                '<' => html_end(rest),
+                _ if rest.starts_with("http://") || rest.starts_with("https://") => {
+                    Some(rest.find(char::is_whitespace).unwrap_or(rest.len()))
+                }
+                _ => None,
+            };
+            if let Some(len) = skip {
+                result.push_str(&rest[..len]);
+                prev = rest[..len].chars().last();
+                i += len;
+                continue;
+            }
+
+            let boundary = prev.is_none_or(|p| !p.is_alphanumeric() && !"&/_-.".contains(p));
+            if let Some((len, link)) = self.reference(rest, is_commit).filter(|_| boundary) {
+                result.push_str(&link);
+                prev = rest[..len].chars().last();
+                i += len;
+                continue;
+            }
+
+            result.push(c);
+            prev = Some(c);
+            i += c.len_utf8();
+        }
+    }
+
+    /// Checks if `text` starts with a reference, returning its length and the
+    /// markdown link for it.
+    fn reference(&self, text: &str, is_commit: &dyn Fn(&str) -> bool) -> Option<(usize, String)> {
+        let word_len = text
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(text.len());
+
+        if let Some(repo) = &self.repo_url {
+            for prefix in &["#", "GH-"] {
+                if let Some(number) = text.strip_prefix(prefix).and_then(number) {
+                    let len = prefix.len() + number.len();
+                    let link = format!("[{}]({}/issues/{})", &text[..len], repo, number);
+                    return Some((len, link));
+                }
+            }
+
+            let word = &text[..word_len];
+            let hex = word
+                .bytes()
+                .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b));
+            if (7..=40).contains(&word.len())
+                && hex
+                && word.bytes().any(|b| b.is_ascii_digit())
+                && is_commit(word)
+            {
+                return Some((word.len(), format!("[{}]({}/commit/{})", word, repo, word)));
+            }
+        }
+
+        self.tickets.iter().find_map(|ticket| {
+            let number = text
+                .strip_prefix(ticket.prefix.as_str())?
+                .strip_prefix('-')
+                .and_then(number)?;
+            let key = &text[..ticket.prefix.len() + 1 + number.len()];
+            Some((
+                key.len(),
+                format!("[{}]({})", key, ticket.url.replace("{key}", key)),
+            ))
+        })
+    }
+}
+
+/// The digits at the start of `text`, if there are any and they aren't
+/// followed by more of a word.
+fn number(text: &str) -> Option<&str> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    match text[end..].chars().next() {
+        _ if end == 0 => None,
+        Some(c) if c.is_alphanumeric() => None,
+        _ => Some(&text[..end]),
+    }
+}
+
+/// The (zero-indexed) lines of every indented code block in `notes`. Unlike
+/// fenced code blocks these depend on the list items around them, so they're
+/// left to comrak to find.
+fn indented_code(notes: &str, opts: &ParseOptions) -> Vec<Range<usize>> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, notes, &opts.comrak());
+    root.descendants()
+        .filter_map(|node| {
+            let nd = node.data.borrow();
+            match nd.value {
+                NodeValue::CodeBlock(ref code) if !code.fenced => {
+                    let start = (nd.start_line as usize).saturating_sub(1);
+                    let len = code.literal.iter().filter(|b| **b == b'\n').count();
+                    Some(start..start + len)
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The length of the HTML tag at the start of `text`. For an anchor this is
+/// the whole element up to its `</a>` (or the end of the line), since its
+/// text is already a link. A `<` that can't start a tag, such as in `a < b`,
+/// isn't one.
+fn html_end(text: &str) -> Option<usize> {
+    if !text[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!') {
+        return None;
+    }
+    let end = text.find('>')? + 1;
+    let name = text[1..]
+        .split(|c: char| c.is_whitespace() || c == '>')
+        .next()
+        .unwrap_or("");
+    if !name.eq_ignore_ascii_case("a") {
+        return Some(end);
+    }
+
+    let close = text[end..].to_ascii_lowercase().find("</a>");
+    Some(close.map_or(text.len(), |i| end + i + "</a>".len()))
+}
+
+/// The length of a bracketed span at the start of `text` along with the link
+/// destination or reference after it, such as `[text](url)` or `[text][ref]`.
+fn bracket_end(text: &str) -> Option<usize> {
+    let close = |text: &str, open: char, close: char| {
+        let mut depth = 0;
+        let mut escaped = false;
+        for (i, c) in text.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                c if c == open => depth += 1,
+                c if c == close => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Some(i + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    };
+
+    let end = close(text, '[', ']')?;
+    let after = &text[end..];
+    let target = match after.chars().next() {
+        Some('(') => close(after, '(', ')'),
+        Some('[') => close(after, '[', ']'),
+        _ => None,
+    };
+    Some(end + target.unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Autolinker, TicketLink};
+
+    #[test]
+    fn link() {
+        let linker = Autolinker {
+            repo_url: Some("https://github.com/yolo/swag".into()),
+            tickets: vec!["WAT=https://example.atlassian.net/browse/{key}"
+                .parse()
+                .unwrap()],
+        };
+        let is_commit = |sha: &str| sha == "abc1234";
+        let opts = Default::default();
+
+        let cases = vec![
+            (
+                "- Fixed #123 and GH-45 (WAT-2392)\n",
+                "- Fixed [#123](https://github.com/yolo/swag/issues/123) and [GH-45](https://github.com/yolo/swag/issues/45) ([WAT-2392](https://example.atlassian.net/browse/WAT-2392))\n",
+            ),
+            (
+                "- Reverted abc1234, not abc1235 or deadbeef\n",
+                "- Reverted [abc1234](https://github.com/yolo/swag/commit/abc1234), not abc1235 or deadbeef\n",
+            ),
+            (
+                "- `#123` [#45](https://example.com) <a href=\"#1\">#1</a> https://example.com/#2\n",
+                "- `#123` [#45](https://example.com) <a href=\"#1\">#1</a> https://example.com/#2\n",
+            ),
+            (
+                "- <A HREF=\"#1\">see <b>#1</b></A> and <b>#2</b>\n",
+                "- <A HREF=\"#1\">see <b>#1</b></A> and <b>[#2](https://github.com/yolo/swag/issues/2)</b>\n",
+            ),
+            ("```\n#123\n```\n", "```\n#123\n```\n"),
+            ("Example:\n\n    #123\n\n#4\n", "Example:\n\n    #123\n\n[#4](https://github.com/yolo/swag/issues/4)\n"),
+            (
+                "- #1\n\n    - #2\n",
+                "- [#1](https://github.com/yolo/swag/issues/1)\n\n    - [#2](https://github.com/yolo/swag/issues/2)\n",
+            ),
+            (
+                "- a < b #1, <= GH-5\n",
+                "- a < b [#1](https://github.com/yolo/swag/issues/1), <= [GH-5](https://github.com/yolo/swag/issues/5)\n",
+            ),
+            (
+                "| a | b |\n|---|---|\n| #1 | `#2` |\n",
+                "| a | b |\n|---|---|\n| [#1](https://github.com/yolo/swag/issues/1) | `#2` |\n",
+            ),
+            ("[#123]: https://example.com\n", "[#123]: https://example.com\n"),
+            ("- #12a, a#12, &#123; and WAT-x\n", "- #12a, a#12, &#123; and WAT-x\n"),
+        ];
+        for (notes, expected) in cases {
+            assert_eq!(linker.link(notes, &opts, &is_commit), expected);
+        }
+
+        let notes = "- Fixed #123\n";
+        assert_eq!(Autolinker::default().link(notes, &opts, &is_commit), notes);
+    }
+
+    #[test]
+    fn ticket_link() {
+        let ticket: TicketLink = "WAT-=https://example.com/{key}".parse().unwrap();
+        assert_eq!(ticket.prefix, "WAT");
+        assert!("WAT=https://example.com/".parse::<TicketLink>().is_err());
+        assert!("https://example.com/{key}".parse::<TicketLink>().is_err());
+    }
+}
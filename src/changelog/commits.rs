@@ -0,0 +1,185 @@
+use super::model::{ReleaseEntry, Section};
+use chrono::NaiveDate;
+
+/// A commit message written in the [Conventional
+/// Commits](https://www.conventionalcommits.org/en/v1.0.0/) format, such as
+/// `feat(parser)!: allow comments`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Commit {
+    /// The type of the change, IE `feat` or `fix`. Always lowercase.
+    pub kind: String,
+    pub scope: Option<String>,
+    /// Set by a `!` before the colon or a `BREAKING CHANGE:` footer.
+    pub breaking: bool,
+    pub description: String,
+    /// The text of the `BREAKING CHANGE:` footer, if there is one.
+    pub breaking_note: Option<String>,
+}
+
+impl Commit {
+    /// Parses a commit message, returning `None` if its first line doesn't
+    /// follow the format.
+    pub fn parse(message: &str) -> Option<Commit> {
+        let mut lines = message.lines();
+        let subject = lines.next()?;
+        let colon = subject.find(": ")?;
+        let (prefix, description) = (&subject[..colon], subject[colon + 2..].trim());
+        let (prefix, bang) = match prefix.strip_suffix('!') {
+            Some(prefix) => (prefix, true),
+            None => (prefix, false),
+        };
+        let (kind, scope) = match prefix.find('(') {
+            Some(i) => (&prefix[..i], Some(prefix[i + 1..].strip_suffix(')')?)),
+            None => (prefix, None),
+        };
+
+        let is_word = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '-');
+        if !is_word(kind) || description.is_empty() {
+            return None;
+        }
+
+        let breaking_note = lines.find_map(|line| {
+            line.strip_prefix("BREAKING CHANGE:")
+                .or_else(|| line.strip_prefix("BREAKING-CHANGE:"))
+                .map(|note| note.trim().to_string())
+        });
+
+        Some(Commit {
+            kind: kind.to_lowercase(),
+            scope: scope.map(|s| s.to_string()),
+            breaking: bang || breaking_note.is_some(),
+            description: description.into(),
+            breaking_note: breaking_note.filter(|n| !n.is_empty()),
+        })
+    }
+
+    /// The Keep a Changelog section this commit is listed under, or `None`
+    /// if it isn't worth mentioning (such as `docs` or `chore`). Breaking
+    /// changes are always listed, under `Changed` unless their type says
+    /// otherwise.
+    pub fn section(&self) -> Option<&'static str> {
+        match self.kind.as_str() {
+            "feat" => Some("Added"),
+            "fix" => Some("Fixed"),
+            "perf" => Some("Changed"),
+            _ if self.breaking => Some("Changed"),
+            _ => None,
+        }
+    }
+
+    /// The list item for this commit, such as `**parser:** allow comments`.
+    fn item(&self, id: &str) -> String {
+        let mut item = String::new();
+        if self.breaking {
+            item.push_str("**BREAKING:** ");
+        }
+        if let Some(scope) = &self.scope {
+            item.push_str(&format!("**{}:** ", scope));
+        }
+        item.push_str(&format!("{} ({})", self.description, id));
+        if let Some(note) = &self.breaking_note {
+            item.push_str(&format!("\n\n{}", note));
+        }
+        item
+    }
+}
+
+/// Builds a release entry for `version` out of commits (short id and message,
+/// newest first, see [git::commits_since](../../git/fn.commits_since.html)).
+/// Commits that don't follow Conventional Commits or have a type that isn't
+/// listed are left out, see [Commit::section](struct.Commit.html#method.section).
+/// `level` is the level of the version heading, so sections go one below it.
+pub fn release_entry(
+    version: &str,
+    date: NaiveDate,
+    commits: &[(String, String)],
+    level: u32,
+) -> ReleaseEntry {
+    let mut sections: Vec<Section> = ["Added", "Changed", "Fixed"]
+        .iter()
+        .map(|name| Section {
+            name: name.to_string(),
+            ..Section::default()
+        })
+        .collect();
+
+    for (id, message) in commits {
+        let commit = match Commit::parse(message) {
+            Some(commit) => commit,
+            None => continue,
+        };
+        if let Some(name) = commit.section() {
+            let section = sections.iter_mut().find(|s| s.name == name).unwrap();
+            section.items.push(commit.item(id));
+        }
+    }
+    sections.retain(|s| !s.items.is_empty());
+
+    ReleaseEntry {
+        level,
+        version: version.into(),
+        date: Some(date),
+        sections,
+        ..ReleaseEntry::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Commit;
+    use crate::version::Level;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parse() {
+        let commit = Commit::parse(
+            "feat(parser)!: allow comments\n\nBREAKING CHANGE: `#` starts a comment now\n",
+        )
+        .unwrap();
+        assert_eq!(commit.kind, "feat");
+        assert_eq!(commit.scope.as_deref(), Some("parser"));
+        assert!(commit.breaking);
+        assert_eq!(commit.description, "allow comments");
+        assert_eq!(
+            commit.breaking_note.as_deref(),
+            Some("`#` starts a comment now")
+        );
+
+        let commit = Commit::parse("Fix: typo").unwrap();
+        assert_eq!((commit.kind.as_str(), commit.breaking), ("fix", false));
+
+        assert_eq!(Commit::parse("Merge branch 'main'"), None);
+        assert_eq!(Commit::parse("fix(: oops"), None);
+        assert_eq!(Commit::parse("update readme: more words"), None);
+    }
+
+    #[test]
+    fn release_entry() {
+        let commits: Vec<(String, String)> = vec![
+            ("a1", "docs: explain things"),
+            ("b2", "fix(cli): exit non-zero on errors"),
+            ("c3", "perf: cache the changelog"),
+            ("d4", "Merge pull request #4"),
+            ("e5", "feat: add a notes command"),
+            ("f6", "refactor!: drop the old config file"),
+        ]
+        .into_iter()
+        .map(|(id, m)| (id.to_string(), m.to_string()))
+        .collect();
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let entry = super::release_entry("1.0.0", date, &commits, 2);
+
+        assert_eq!(
+            entry.notes(),
+            "### Added\n\n- add a notes command (e5)\n\n### Changed\n\n- cache the changelog (c3)\n- **BREAKING:** drop the old config file (f6)\n\n### Fixed\n\n- **cli:** exit non-zero on errors (b2)\n"
+        );
+        assert_eq!(entry.change_level().unwrap().0, Level::Major);
+        assert!(super::release_entry("1.0.0", date, &[], 2).is_empty());
+
+        let entry = super::release_entry("1.0.0", date, &commits[..2], 3);
+        assert_eq!(
+            entry.to_string(),
+            "### 1.0.0 - 2020-06-01\n\n#### Fixed\n\n- **cli:** exit non-zero on errors (b2)\n"
+        );
+    }
+}
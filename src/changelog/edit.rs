@@ -0,0 +1,392 @@
+use super::model::{
+    line_offset, link_definition, top_level_blocks, Block, BlockKind, Heading, ParseOptions,
+};
+use crate::git::TagTemplate;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+
+/// A change to the text of a changelog: the byte offset it starts at, how many
+/// bytes it replaces and what it replaces them with.
+type Edit = (usize, usize, String);
+
+/// Moves everything in the `Unreleased` section of a changelog under a new
+/// heading for `version` dated `date`, leaving an empty `Unreleased` section
+/// above it. If the `Unreleased` heading has a GitHub style compare link
+/// (`[Unreleased]: https://github.com/foo/bar/compare/v0.1.0...HEAD`), it is
+/// moved along to the new tag and a compare link for the new version is added
+/// after it, with tags named by `tags`. Everything else in the file is left
+/// alone byte for byte.
+pub fn promote_unreleased(
+    data: &str,
+    version: &str,
+    date: NaiveDate,
+    opts: &ParseOptions,
+    tags: &TagTemplate,
+) -> Result<String> {
+    let lines: Vec<&str> = data.lines().collect();
+    let blocks = top_level_blocks(data, opts);
+    let releases = releases(&blocks, version, opts)?;
+
+    let position = releases
+        .iter()
+        .position(|(_, hdr)| hdr.version.eq_ignore_ascii_case("unreleased"))
+        .ok_or_else(|| anyhow!("the changelog has no Unreleased section"))?;
+    let unreleased = releases[position].0;
+    let body_start = blocks[unreleased].body_start();
+    let body_end = blocks[unreleased + 1..]
+        .iter()
+        .find(|b| matches!(b.heading_level(), Some(level) if level <= opts.version_level))
+        .map(|b| b.start)
+        .unwrap_or_else(|| lines.len());
+
+    let is_empty = lines[body_start.min(lines.len())..body_end]
+        .iter()
+        .all(|l| l.trim().is_empty() || link_definition(l).is_some());
+    if is_empty {
+        return Err(anyhow!(
+            "the Unreleased section is empty, there is nothing to release"
+        ));
+    }
+
+    let mut edits: Vec<Edit> = vec![];
+    let mut insert_at = line_offset(data, body_start);
+    if insert_at == data.len() && !data.ends_with('\n') {
+        edits.push((insert_at, 0, "\n".into()));
+        insert_at = data.len();
+    }
+    edits.push((
+        insert_at,
+        0,
+        format!("\n{}\n", heading(&blocks, &releases, version, date, opts)),
+    ));
+    edits.extend(compare_links(data, previous(&releases), version, tags)?);
+
+    Ok(apply(data, edits))
+}
+
+/// Adds an empty heading for `version` dated `date` below the `Unreleased`
+/// section (or above the newest version if there is no `Unreleased` section),
+/// ready for release notes to be written under it. Compare links are updated
+/// like [promote_unreleased](fn.promote_unreleased.html) does.
+pub fn insert_heading(
+    data: &str,
+    version: &str,
+    date: NaiveDate,
+    opts: &ParseOptions,
+    tags: &TagTemplate,
+) -> Result<String> {
+    insert_release(data, version, date, "", opts, tags)
+}
+
+/// Adds a heading for `version` dated `date` with `notes` under it, in the
+/// same place [insert_heading](fn.insert_heading.html) does.
+pub fn insert_release(
+    data: &str,
+    version: &str,
+    date: NaiveDate,
+    notes: &str,
+    opts: &ParseOptions,
+    tags: &TagTemplate,
+) -> Result<String> {
+    let blocks = top_level_blocks(data, opts);
+    let releases = releases(&blocks, version, opts)?;
+    let mut heading = heading(&blocks, &releases, version, date, opts);
+    if !notes.trim().is_empty() {
+        heading.push_str(&format!("\n\n{}", notes.trim_end()));
+    }
+
+    let next = releases
+        .iter()
+        .find(|(_, hdr)| !hdr.version.eq_ignore_ascii_case("unreleased"));
+    let mut edits: Vec<Edit> = match next {
+        Some((i, _)) => vec![(
+            line_offset(data, blocks[*i].start),
+            0,
+            format!("{}\n\n", heading),
+        )],
+        None => {
+            let sep = match (data.ends_with("\n\n"), data.ends_with('\n')) {
+                (true, _) => "",
+                (false, true) => "\n",
+                (false, false) => "\n\n",
+            };
+            vec![(data.len(), 0, format!("{}{}\n", sep, heading))]
+        }
+    };
+    edits.extend(compare_links(data, previous(&releases), version, tags)?);
+
+    Ok(apply(data, edits))
+}
+
+/// Returns the index and parsed heading of every version heading, failing if
+/// `version` is already one of them.
+fn releases(blocks: &[Block], version: &str, opts: &ParseOptions) -> Result<Vec<(usize, Heading)>> {
+    let releases: Vec<(usize, Heading)> = blocks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, b)| Some((i, b.version_heading(opts)?)))
+        .collect();
+
+    if releases.iter().any(|(_, hdr)| hdr.version == version) {
+        return Err(anyhow!("version {} is already in the changelog", version));
+    }
+
+    Ok(releases)
+}
+
+/// The newest version in the changelog, not counting `Unreleased`.
+fn previous(releases: &[(usize, Heading)]) -> Option<&str> {
+    releases
+        .iter()
+        .map(|(_, hdr)| hdr.version.as_str())
+        .find(|v| !v.eq_ignore_ascii_case("unreleased"))
+}
+
+/// Formats a heading for a new version, bracketing the version if the
+/// `Unreleased` heading (or failing that, the newest version) is bracketed.
+/// With a heading pattern, the newest version heading is copied with the
+/// version and date swapped out (and any `[YANKED]` marker dropped) instead.
+fn heading(
+    blocks: &[Block],
+    releases: &[(usize, Heading)],
+    version: &str,
+    date: NaiveDate,
+    opts: &ParseOptions,
+) -> String {
+    let hashes = "#".repeat(opts.version_level as usize);
+    if let Some(pattern) = &opts.heading_pattern {
+        let newest = releases
+            .iter()
+            .find(|(_, hdr)| !hdr.version.eq_ignore_ascii_case("unreleased"));
+        let text = match newest.map(|(i, hdr)| (&blocks[*i].kind, hdr)) {
+            Some((BlockKind::Heading { text, .. }, hdr)) => {
+                // a new release isn't yanked, whatever happened to the last one
+                let text = match hdr.yanked {
+                    true => text[..text.len() - "[YANKED]".len()].trim_end(),
+                    false => text.as_str(),
+                };
+                let text = text.replacen(&hdr.version, version, 1);
+                match hdr.date {
+                    Some(old) => text.replacen(&old.to_string(), &date.to_string(), 1),
+                    None => text,
+                }
+            }
+            _ => pattern.render(version, date),
+        };
+        return format!("{} {}", hashes, text);
+    }
+
+    let example = releases
+        .iter()
+        .find(|(_, hdr)| hdr.version.eq_ignore_ascii_case("unreleased"))
+        .or_else(|| releases.first());
+    let bracketed = match example.map(|(i, _)| &blocks[*i].kind) {
+        Some(BlockKind::Heading { text, .. }) => text.starts_with('['),
+        _ => false,
+    };
+
+    match bracketed {
+        true => format!("{} [{}] - {}", hashes, version, date),
+        false => format!("{} {} - {}", hashes, version, date),
+    }
+}
+
+/// Moves the `Unreleased` compare link (if there is one) along to the tag for
+/// `version` and adds a compare link for `version` after it. `previous` is
+/// the newest version already in the changelog, the link compares from its
+/// tag. If there is no previous version (or it isn't a valid version), the tag
+/// already in the link is kept.
+fn compare_links(
+    data: &str,
+    previous: Option<&str>,
+    version: &str,
+    tags: &TagTemplate,
+) -> Result<Option<Edit>> {
+    let found = data
+        .lines()
+        .enumerate()
+        .find_map(|(n, line)| match link_definition(line) {
+            Some((label, url)) if label.eq_ignore_ascii_case("unreleased") => {
+                Some((n, line, label, split_compare_link(url)?))
+            }
+            _ => None,
+        });
+    let (n, line, label, (compare, old_tag)) = match found {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+
+    let new_tag = tags.tag(version)?;
+    let old_tag = previous
+        .and_then(|previous| tags.tag(previous).ok())
+        .unwrap_or_else(|| old_tag.to_string());
+
+    Ok(Some((
+        line_offset(data, n),
+        line.len(),
+        format!(
+            "[{}]: {}{}...HEAD\n[{}]: {}{}...{}",
+            label, compare, new_tag, version, compare, old_tag, new_tag
+        ),
+    )))
+}
+
+/// Applies edits back to front so their offsets stay valid.
+fn apply(data: &str, mut edits: Vec<Edit>) -> String {
+    edits.sort_by_key(|(offset, _, _)| *offset);
+    let mut result = data.to_string();
+    for (offset, len, text) in edits.into_iter().rev() {
+        result.replace_range(offset..offset + len, &text);
+    }
+
+    result
+}
+
+/// Splits `https://github.com/foo/bar/compare/v0.1.0...HEAD` into
+/// `https://github.com/foo/bar/compare/` and `v0.1.0`.
+fn split_compare_link(url: &str) -> Option<(&str, &str)> {
+    let base = url.strip_suffix("...HEAD")?;
+    let split = base
+        .find("/compare/")
+        .map(|i| i + "/compare/".len())
+        .or_else(|| base.rfind('/').map(|i| i + 1))?;
+    Some((&base[..split], &base[split..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn promote_unreleased() {
+        let data = read_to_string("testdata/keepachangelog.md").unwrap();
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let result = super::promote_unreleased(
+            &data,
+            "1.2.0",
+            date,
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+
+        let expected = data
+            .replace(
+                "## [Unreleased]\n",
+                "## [Unreleased]\n\n## [1.2.0] - 2020-06-01\n",
+            )
+            .replace(
+                "[unreleased]: https://github.com/yolo/swag/compare/v1.1.0...HEAD\n",
+                "[unreleased]: https://github.com/yolo/swag/compare/v1.2.0...HEAD\n[1.2.0]: https://github.com/yolo/swag/compare/v1.1.0...v1.2.0\n",
+            );
+        assert_eq!(result, expected);
+
+        let err = super::promote_unreleased(
+            &result,
+            "1.3.0",
+            date,
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the Unreleased section is empty, there is nothing to release"
+        );
+        let err = super::promote_unreleased(
+            &data,
+            "1.1.0",
+            date,
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "version 1.1.0 is already in the changelog");
+    }
+
+    #[test]
+    fn insert_heading() {
+        let data = read_to_string("testdata/keepachangelog.md").unwrap();
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let result = super::insert_heading(
+            &data,
+            "1.2.0",
+            date,
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+
+        let expected = data
+            .replace("## [1.1.0]", "## [1.2.0] - 2020-06-01\n\n## [1.1.0]")
+            .replace(
+                "[unreleased]: https://github.com/yolo/swag/compare/v1.1.0...HEAD\n",
+                "[unreleased]: https://github.com/yolo/swag/compare/v1.2.0...HEAD\n[1.2.0]: https://github.com/yolo/swag/compare/v1.1.0...v1.2.0\n",
+            );
+        assert_eq!(result, expected);
+
+        let result = super::insert_heading(
+            "# Changelog\n",
+            "0.1.0",
+            date,
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(result, "# Changelog\n\n## 0.1.0 - 2020-06-01\n");
+
+        let result = super::insert_release(
+            "# Changelog\n",
+            "0.1.0",
+            date,
+            "### Added\n\n- something\n",
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            "# Changelog\n\n## 0.1.0 - 2020-06-01\n\n### Added\n\n- something\n"
+        );
+    }
+
+    #[test]
+    fn compare_links_tag_template() {
+        let data = "## [Unreleased]\n\n## [1.10.0]\n\n[unreleased]: https://github.com/yolo/swag/compare/v1.10.0...HEAD\n";
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let template =
+            crate::git::TagTemplate::new("{name}/{version}", "yolo", Default::default()).unwrap();
+        let result =
+            super::insert_heading(data, "1.11.0", date, &Default::default(), &template).unwrap();
+
+        assert!(result.ends_with(
+            "[unreleased]: https://github.com/yolo/swag/compare/yolo/1.11.0...HEAD\n[1.11.0]: https://github.com/yolo/swag/compare/yolo/1.10.0...yolo/1.11.0\n"
+        ));
+    }
+
+    #[test]
+    fn heading_pattern() {
+        let data = read_to_string("testdata/headings.md").unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        let opts = crate::changelog::ParseOptions {
+            version_level: 3,
+            heading_pattern: Some(r"Version {version} \({date}\)".parse().unwrap()),
+            ..Default::default()
+        };
+        let result =
+            super::promote_unreleased(&data, "1.3.0", date, &opts, &Default::default()).unwrap();
+        assert_eq!(
+            result,
+            data.replace(
+                "### Unreleased\n",
+                "### Unreleased\n\n### Version 1.3.0 (2024-06-01)\n"
+            )
+        );
+
+        let yanked = data.replace("(2024-05-01)", "(2024-05-01) [YANKED]");
+        let result =
+            super::promote_unreleased(&yanked, "1.3.0", date, &opts, &Default::default()).unwrap();
+        assert!(result.contains("### Unreleased\n\n### Version 1.3.0 (2024-06-01)\n\n"));
+    }
+}
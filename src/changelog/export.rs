@@ -0,0 +1,216 @@
+use super::model::ReleaseEntry;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset};
+use std::{fmt, str::FromStr};
+
+/// The changelog formats of Linux distribution packages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// `debian/changelog`, see
+    /// https://www.debian.org/doc/debian-policy/ch-source.html#debian-changelog-debian-changelog
+    Debian,
+    /// The `%changelog` section of an RPM spec file, see
+    /// https://rpm-software-management.github.io/rpm/manual/spec.html#changelog-section
+    Rpm,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "debian" => Ok(Format::Debian),
+            "rpm" => Ok(Format::Rpm),
+            _ => Err(anyhow!(
+                "unknown export format {:?}, expected debian or rpm",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Debian => write!(f, "debian"),
+            Format::Rpm => write!(f, "rpm"),
+        }
+    }
+}
+
+/// Who packaged the releases and how, for the parts of a package changelog
+/// that Keep a Changelog doesn't have.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Package {
+    /// The source package name, only used by Debian.
+    pub name: String,
+    /// The packager, as `Name <email>`.
+    pub maintainer: String,
+    /// The Debian distribution the releases are uploaded to, IE `unstable`.
+    pub distribution: String,
+    /// How important it is to upgrade, IE `medium`. Only used by Debian.
+    pub urgency: String,
+    /// The package revision appended to every version, IE the `1` of
+    /// `1.2.0-1`.
+    pub revision: String,
+}
+
+/// Renders release entries, newest first and each with the time it was
+/// released, as a package changelog.
+pub fn render(
+    format: Format,
+    package: &Package,
+    entries: &[(&ReleaseEntry, DateTime<FixedOffset>)],
+) -> String {
+    let mut result = String::new();
+    for (entry, time) in entries {
+        let version = package_version(&entry.version, &package.revision);
+        let changes = changes(entry);
+        match format {
+            Format::Debian => {
+                result.push_str(&format!(
+                    "{} ({}) {}; urgency={}\n\n",
+                    package.name, version, package.distribution, package.urgency
+                ));
+                for change in changes {
+                    result.push_str(&wrap(&change, "  * ", "    "));
+                }
+                result.push_str(&format!(
+                    "\n -- {}  {}\n\n",
+                    package.maintainer,
+                    time.format("%a, %d %b %Y %H:%M:%S %z")
+                ));
+            }
+            Format::Rpm => {
+                result.push_str(&format!(
+                    "* {} {} - {}\n",
+                    time.format("%a %b %d %Y"),
+                    package.maintainer,
+                    version
+                ));
+                for change in changes {
+                    result.push_str(&wrap(&change, "- ", "  "));
+                }
+                result.push('\n');
+            }
+        }
+    }
+
+    result.truncate(result.trim_end().len());
+    result.push('\n');
+    result
+}
+
+/// Turns a version into a package version: prereleases sort before the
+/// release with `~` instead of `-` (`2.0.0-rc.1` is `2.0.0~rc.1`), and the
+/// package revision goes after a `-`.
+fn package_version(version: &str, revision: &str) -> String {
+    let version = version.replacen('-', "~", 1);
+    match revision {
+        "" => version,
+        revision => format!("{}-{}", version, revision),
+    }
+}
+
+/// The changes in an entry as one line of text each: the description, then
+/// every item prefixed with its section, such as `Fixed: typos`.
+fn changes(entry: &ReleaseEntry) -> Vec<String> {
+    let flatten = |text: &str| text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut result: Vec<String> = entry
+        .description
+        .split("\n\n")
+        .map(flatten)
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    for section in &entry.sections {
+        let blocks = section.blocks.iter().map(|(_, text)| text);
+        for item in section.items.iter().chain(blocks) {
+            result.push(format!("{}: {}", section.name, flatten(item)));
+        }
+    }
+
+    if result.is_empty() {
+        result.push("New upstream release.".into());
+    }
+    result
+}
+
+/// Wraps `text` to 80 columns, starting the first line with `first` and the
+/// rest with `rest`.
+fn wrap(text: &str, first: &str, rest: &str) -> String {
+    let mut result = String::new();
+    let mut line = first.to_string();
+    let mut words = 0;
+    for word in text.split_whitespace() {
+        if words > 0 && line.len() + 1 + word.len() > 80 {
+            result.push_str(&line);
+            result.push('\n');
+            line = rest.to_string();
+            words = 0;
+        }
+        if words > 0 {
+            line.push(' ');
+        }
+        line.push_str(word);
+        words += 1;
+    }
+    result.push_str(&line);
+    result.push('\n');
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Format, Package};
+    use crate::changelog::{Changelog, ParseOptions};
+    use chrono::DateTime;
+
+    #[test]
+    fn render() {
+        let data = "# Changelog\n\n## [1.1.0-rc.1] - 2020-06-01\n\nA release candidate.\n\n### Added\n\n- Support for frobnicating strings that are so long that they need to be\n  wrapped onto a second line\n\n### Fixed\n\n- Typos\n\n## [1.0.0]\n";
+        let cl = Changelog::parse(data, &ParseOptions::default()).unwrap();
+        let times = vec![
+            DateTime::parse_from_rfc3339("2020-06-01T00:00:00+00:00").unwrap(),
+            DateTime::parse_from_rfc3339("2020-05-04T13:30:00+02:00").unwrap(),
+        ];
+        let entries: Vec<_> = cl.releases.iter().zip(times).collect();
+        let package = Package {
+            name: "palisade".into(),
+            maintainer: "Jane Doe <jane@example.com>".into(),
+            distribution: "unstable".into(),
+            urgency: "medium".into(),
+            revision: "1".into(),
+        };
+
+        assert_eq!(
+            super::render(Format::Debian, &package, &entries),
+            "palisade (1.1.0~rc.1-1) unstable; urgency=medium
+
+  * A release candidate.
+  * Added: Support for frobnicating strings that are so long that they need to
+    be wrapped onto a second line
+  * Fixed: Typos
+
+ -- Jane Doe <jane@example.com>  Mon, 01 Jun 2020 00:00:00 +0000
+
+palisade (1.0.0-1) unstable; urgency=medium
+
+  * New upstream release.
+
+ -- Jane Doe <jane@example.com>  Mon, 04 May 2020 13:30:00 +0200
+"
+        );
+        assert_eq!(
+            super::render(Format::Rpm, &package, &entries),
+            "* Mon Jun 01 2020 Jane Doe <jane@example.com> - 1.1.0~rc.1-1
+- A release candidate.
+- Added: Support for frobnicating strings that are so long that they need to be
+  wrapped onto a second line
+- Fixed: Typos
+
+* Mon May 04 2020 Jane Doe <jane@example.com> - 1.0.0-1
+- New upstream release.
+"
+        );
+    }
+}
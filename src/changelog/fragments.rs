@@ -0,0 +1,171 @@
+use super::model::{ReleaseEntry, Section};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// The kinds of fragment, as `(file name type, section name)`, in the order
+/// Keep a Changelog lists the sections.
+const KINDS: &[(&str, &str)] = &[
+    ("added", "Added"),
+    ("changed", "Changed"),
+    ("deprecated", "Deprecated"),
+    ("removed", "Removed"),
+    ("fixed", "Fixed"),
+    ("security", "Security"),
+];
+
+/// One change written to its own file instead of the `Unreleased` section,
+/// such as `changes/1234.added.md`, so that pull requests don't all edit the
+/// same lines of the changelog. The file is named after an issue number or any
+/// other unique name, then the kind of change.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fragment {
+    pub path: PathBuf,
+    /// The part of the file name before the kind, IE `1234`.
+    pub id: String,
+    /// The section the change goes under, IE `Added`.
+    pub section: &'static str,
+    /// The contents of the file, without a leading list marker.
+    pub text: String,
+}
+
+impl Fragment {
+    /// Reads a fragment. Returns `None` for files that aren't fragments at
+    /// all (not markdown, hidden or without a kind, such as `README.md`) and
+    /// an error for fragments of an unknown kind.
+    pub fn read(path: &Path) -> Result<Option<Fragment>> {
+        let (id, kind) = match fragment_name(path) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let section = KINDS
+            .iter()
+            .find(|(k, _)| *k == kind)
+            .map(|(_, section)| *section)
+            .ok_or_else(|| {
+                anyhow!(
+                    "{}: unknown kind of change {:?}, expected added, changed, deprecated, removed, fixed or security",
+                    path.display(),
+                    kind
+                )
+            })?;
+
+        let text = fs::read_to_string(path)?;
+        let text = text.trim();
+        let text = text
+            .strip_prefix("- ")
+            .or_else(|| text.strip_prefix("* "))
+            .unwrap_or(text);
+        if text.is_empty() {
+            return Err(anyhow!("{}: the fragment is empty", path.display()));
+        }
+
+        Ok(Some(Fragment {
+            path: path.into(),
+            id: id.into(),
+            section,
+            text: text.into(),
+        }))
+    }
+}
+
+/// Splits `1234.added.md` into `1234` and `added`.
+fn fragment_name(path: &Path) -> Option<(&str, &str)> {
+    let name = path.file_name()?.to_str()?;
+    if name.starts_with('.') {
+        return None;
+    }
+    let stem = name.strip_suffix(".md")?;
+    let dot = stem.rfind('.')?;
+    Some((&stem[..dot], &stem[dot + 1..]))
+}
+
+/// Reads every fragment in `dir`, ordered by id (numerically for issue
+/// numbers). A directory that doesn't exist has no fragments.
+pub fn read_dir(dir: &Path) -> Result<Vec<Fragment>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(why) if why.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(why) => return Err(why.into()),
+    };
+
+    let mut result = vec![];
+    for entry in entries {
+        let path = entry?.path();
+        if path.is_file() {
+            result.extend(Fragment::read(&path)?);
+        }
+    }
+
+    result.sort_by_key(|f| (f.id.parse::<u64>().unwrap_or(u64::MAX), f.id.clone()));
+    Ok(result)
+}
+
+/// Builds a release entry for `version` out of fragments, with a section for
+/// each kind of change that has any. `level` is the level of the version
+/// heading, so sections go one below it.
+pub fn release_entry(
+    version: &str,
+    date: NaiveDate,
+    fragments: &[Fragment],
+    level: u32,
+) -> ReleaseEntry {
+    let sections = KINDS
+        .iter()
+        .map(|(_, name)| Section {
+            name: name.to_string(),
+            items: fragments
+                .iter()
+                .filter(|f| f.section == *name)
+                .map(|f| f.text.clone())
+                .collect(),
+            ..Section::default()
+        })
+        .filter(|s| !s.items.is_empty())
+        .collect();
+
+    ReleaseEntry {
+        level,
+        version: version.into(),
+        date: Some(date),
+        sections,
+        ..ReleaseEntry::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use std::path::Path;
+
+    #[test]
+    fn read_dir() {
+        let fragments = super::read_dir(Path::new("testdata/fragments")).unwrap();
+        let ids: Vec<&str> = fragments.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec!["9", "1234", "frobnicate"]);
+
+        let date = NaiveDate::from_ymd_opt(2020, 6, 1).unwrap();
+        let entry = super::release_entry("1.2.0", date, &fragments, 3);
+        assert_eq!(
+            entry.notes(),
+            "#### Added\n\n- Support for frobnicating\n\n#### Fixed\n\n- Typos in the help text\n- Refrobnicating already frobnicated strings works\n  again.\n"
+        );
+
+        assert!(super::read_dir(Path::new("testdata/missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("12.fix.md"), "Oops\n").unwrap();
+        let err = super::read_dir(dir.path()).unwrap_err();
+        assert!(err
+            .to_string()
+            .ends_with("12.fix.md: unknown kind of change \"fix\", expected added, changed, deprecated, removed, fixed or security"));
+    }
+}
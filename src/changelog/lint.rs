@@ -0,0 +1,331 @@
+use super::model::{top_level_blocks, BlockKind, Changelog, Heading, ParseOptions};
+use crate::version::{self, Scheme};
+use anyhow::Result;
+use semver::Version;
+use std::{collections::HashMap, fmt, fs::read_to_string, path::PathBuf};
+
+/// The section names [Keep a Changelog](https://keepachangelog.com/en/1.0.0/)
+/// allows under a version heading. These are compared ignoring case.
+pub const SECTION_NAMES: &[&str] = &[
+    "Added",
+    "Changed",
+    "Deprecated",
+    "Removed",
+    "Fixed",
+    "Security",
+];
+
+/// A problem found in a changelog, along with the (one-indexed) line it is on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.line, self.message)
+    }
+}
+
+/// Lints a changelog file on disk. See [check](fn.check.html) for details.
+pub fn lint<T>(fname: T, opts: &ParseOptions, scheme: &Scheme) -> Result<Vec<Diagnostic>>
+where
+    T: Into<PathBuf>,
+{
+    let data = read_to_string(fname.into())?;
+    Ok(check(&data, opts, scheme))
+}
+
+/// Checks a changelog against the Keep a Changelog conventions palisade relies
+/// on:
+///
+/// - there is exactly one first level heading
+/// - versions are only used as second level headings (or the level in `opts`)
+/// - every version heading is a valid version in `scheme` (or `Unreleased`),
+///   optionally followed by an ISO 8601 date
+/// - third level headings are one of the [allowed section names](constant.SECTION_NAMES.html)
+/// - versions are listed newest first and are not repeated
+/// - there is an `Unreleased` section
+pub fn check(data: &str, opts: &ParseOptions, scheme: &Scheme) -> Vec<Diagnostic> {
+    let mut result = vec![];
+    let mut report = |line: usize, message: String| {
+        result.push(Diagnostic {
+            line: line + 1,
+            message,
+        })
+    };
+
+    let mut title_line: Option<usize> = None;
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    let mut previous: Option<(Version, usize)> = None;
+    let mut in_release = false;
+    let mut has_unreleased = false;
+
+    for block in top_level_blocks(data, opts) {
+        let (level, text) = match &block.kind {
+            BlockKind::Heading { level, text, .. } => (*level, text),
+            _ => continue,
+        };
+        let heading = block.version_heading(opts);
+        let is_version = |version: &str| {
+            scheme.parse(version).is_ok() || version.eq_ignore_ascii_case("unreleased")
+        };
+        // with first level version headings, the title is the first heading
+        // that isn't a version
+        let is_title = level == 1
+            && match &heading {
+                Some(hdr) => !in_release && !is_version(&hdr.version),
+                None => true,
+            };
+
+        if is_title {
+            match title_line {
+                Some(first) => report(
+                    block.start,
+                    format!(
+                        "extra first level heading, the title is on line {}",
+                        first + 1
+                    ),
+                ),
+                None => title_line = Some(block.start),
+            }
+        } else if let Some(Heading { version, rest, .. }) = heading {
+            in_release = true;
+
+            if !rest.is_empty() {
+                report(
+                    block.start,
+                    format!(
+                        "unexpected {:?} after the version, expected a date like 2020-05-01",
+                        rest
+                    ),
+                );
+            }
+
+            if version.eq_ignore_ascii_case("unreleased") {
+                has_unreleased = true;
+                continue;
+            }
+
+            if let Some(first) = seen.insert(version.clone(), block.start) {
+                report(
+                    block.start,
+                    format!(
+                        "version {} is already listed on line {}",
+                        version,
+                        first + 1
+                    ),
+                );
+                continue;
+            }
+
+            let parsed = match scheme.parse(&version) {
+                Ok(parsed) => parsed,
+                Err(why) => {
+                    report(block.start, why.to_string());
+                    continue;
+                }
+            };
+
+            if let Some((prev, prev_line)) = &previous {
+                if parsed >= *prev {
+                    report(
+                        block.start,
+                        format!(
+                            "version {} should be listed before version {} on line {}, newest versions go first",
+                            scheme.format(&parsed),
+                            scheme.format(prev),
+                            prev_line + 1
+                        ),
+                    );
+                }
+            }
+            previous = Some((parsed, block.start));
+        } else {
+            let version = match &opts.heading_pattern {
+                Some(pattern) => pattern.parse(text).unwrap_or_else(|| Heading::parse(text)),
+                None => Heading::parse(text),
+            }
+            .version;
+            if level != opts.version_level && is_version(&version) {
+                report(
+                    block.start,
+                    format!(
+                        "version heading {:?} should be a {} level heading",
+                        text,
+                        ordinal(opts.version_level)
+                    ),
+                );
+                continue;
+            }
+
+            if level <= opts.version_level {
+                // a heading that groups versions, such as a year
+                in_release = false;
+                continue;
+            }
+            if level != opts.version_level + 1 {
+                continue;
+            }
+
+            if !in_release {
+                report(
+                    block.start,
+                    format!("section {:?} is not under a version heading", text),
+                );
+            } else if !SECTION_NAMES
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(text.trim()))
+            {
+                report(
+                    block.start,
+                    format!(
+                        "unknown section {:?}, expected one of {}",
+                        text,
+                        SECTION_NAMES.join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    if title_line.is_none() {
+        report(0, "missing a first level heading for the title".into());
+    }
+    if !has_unreleased {
+        report(0, "missing an Unreleased section".into());
+    }
+
+    result.sort_by_key(|d| d.line);
+    result
+}
+
+/// Names a heading level for messages, IE `second` for `##`.
+fn ordinal(level: u32) -> &'static str {
+    match level {
+        1 => "first",
+        2 => "second",
+        3 => "third",
+        4 => "fourth",
+        5 => "fifth",
+        _ => "sixth",
+    }
+}
+
+/// Checks that every version in a changelog is bumped enough from the version
+/// below it for the changes listed under it, see
+/// [check_bump](../../version/fn.check_bump.html). Versions that aren't valid
+/// semantic versions are skipped, [check](fn.check.html) reports those.
+pub fn bumps(data: &str, opts: &ParseOptions) -> Vec<Diagnostic> {
+    let cl = match Changelog::parse(data, opts) {
+        Ok(cl) => cl,
+        Err(_) => return vec![],
+    };
+    let lines: HashMap<String, usize> = top_level_blocks(data, opts)
+        .into_iter()
+        .filter_map(|b| Some((b.version_heading(opts)?.version, b.start)))
+        .collect();
+
+    let versions: Vec<(Version, &_)> = cl
+        .releases
+        .iter()
+        .filter_map(|r| Some((Version::parse(&r.version).ok()?, r)))
+        .collect();
+
+    versions
+        .windows(2)
+        .filter_map(|pair| {
+            let ((next, entry), (previous, _)) = (&pair[0], &pair[1]);
+            let (level, reason) = entry.change_level()?;
+            let why = version::check_bump(previous, next, level, &reason).err()?;
+            Some(Diagnostic {
+                line: lines.get(&entry.version).map_or(0, |l| l + 1),
+                message: why.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn clean() {
+        let diags = super::lint(
+            "testdata/keepachangelog.md",
+            &Default::default(),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(diags, vec![]);
+    }
+
+    #[test]
+    fn heading_pattern() {
+        let opts = crate::changelog::ParseOptions {
+            version_level: 3,
+            heading_pattern: Some(r"Version {version} \({date}\)".parse().unwrap()),
+            ..Default::default()
+        };
+        let diags = super::lint("testdata/headings.md", &opts, &Default::default()).unwrap();
+        assert_eq!(diags, vec![]);
+
+        let data = "# Changelog\n\n### Unreleased\n\n## Version 1.0.0 (2024-05-01)\n";
+        let diags: Vec<String> = super::check(data, &opts, &Default::default())
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diags,
+            vec![
+                "5: version heading \"Version 1.0.0 (2024-05-01)\" should be a third level heading"
+            ]
+        );
+    }
+
+    #[test]
+    fn problems() {
+        let diags: Vec<String> =
+            super::lint("testdata/lint.md", &Default::default(), &Default::default())
+                .unwrap()
+                .iter()
+                .map(|d| d.to_string())
+                .collect();
+
+        assert_eq!(
+            diags,
+            vec![
+                "1: missing an Unreleased section",
+                "3: extra first level heading, the title is on line 1",
+                "5: \"1.0\" is not a valid semantic version: unexpected end of input while parsing minor version number",
+                "7: unknown section \"Improved\", expected one of Added, Changed, Deprecated, Removed, Fixed, Security",
+                "9: unexpected \"May 1st\" after the version, expected a date like 2020-05-01",
+                "11: version 0.3.0 should be listed before version 0.2.0 on line 9, newest versions go first",
+                "13: version 0.2.0 is already listed on line 9",
+                "15: version heading \"0.1.0\" should be a second level heading",
+            ]
+        );
+    }
+
+    #[test]
+    fn bumps() {
+        let data = std::fs::read_to_string("testdata/keepachangelog.md").unwrap();
+        assert_eq!(super::bumps(&data, &Default::default()), vec![]);
+
+        let data = "## 1.2.0\n\n### Removed\n\n- frobnication\n\n## 1.1.1\n\n### Added\n\n- frobnication\n\n## 1.1.0\n";
+        let diags: Vec<String> = super::bumps(data, &Default::default())
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(
+            diags,
+            vec![
+                "1: version 1.2.0 is a minor release after 1.1.1, but its changelog entry needs a major release (changes under Removed)",
+                "7: version 1.1.1 is a patch release after 1.1.0, but its changelog entry needs a minor release (changes under Added)",
+            ]
+        );
+
+        let data = "## 2.0.0\n\n### Removed\n\n- frobnication\n\n## 2.0.0-rc.1\n\n### Removed\n\n- frobnication\n\n## 1.1.0\n";
+        assert_eq!(super::bumps(data, &Default::default()), vec![]);
+    }
+}
@@ -0,0 +1,332 @@
+use crate::version::Scheme;
+use anyhow::{anyhow, Result};
+use model::{line_offset, link_definition, link_definitions, same_version, top_level_blocks};
+use semver::Version;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+
+pub mod autolink;
+pub mod commits;
+pub mod edit;
+pub mod export;
+pub mod fragments;
+pub mod lint;
+mod model;
+
+pub use model::{Changelog, Heading, HeadingPattern, ParseOptions, ReleaseEntry, Section};
+
+/** Read changelog entries based on a given tag. Given a changelog that looks like this:
+
+```markdown
+# Changelog
+All notable changes to this project will be documented in this file.
+
+The format is based on [Keep a Changelog](https://keepachangelog.com/en/1.0.0/),
+and this project adheres to [Semantic Versioning](https://semver.org/spec/v2.0.0.html).
+
+## 0.1.0
+
+### FIXED
+
+- Refrobnicate the spurious rilkefs
+
+## 0.0.1
+
+First release, proof of concept.
+```
+
+When the tag 0.1.0 is passed, this function will return exactly what was
+written under that heading:
+
+```markdown
+### FIXED
+
+- Refrobnicate the spurious rilkefs
+```
+
+The heading can also be bracketed, linked and dated the way Keep a Changelog
+suggests, so `## [0.1.0](https://example.com) - 2020-05-01` matches the tag
+0.1.0 too. See [Heading](struct.Heading.html).
+*/
+pub(crate) fn read<T, U>(fname: T, tag: U, opts: &ParseOptions) -> Result<String>
+where
+    T: Into<PathBuf>,
+    U: Into<String>,
+{
+    let data = read_to_string(fname.into())?;
+    Ok(extract(&data, &tag.into(), opts))
+}
+
+/// Slices the notes for a tag out of the text of a changelog, see
+/// [read](fn.read.html). Nothing is re-rendered: the notes are the exact bytes
+/// between the matching version heading and the next version heading, minus
+/// surrounding blank lines. Link reference definitions at the bottom of the
+/// changelog are left out unless the notes use them, in which case they are
+/// added after the notes so the links still work.
+fn extract(data: &str, tag: &str, opts: &ParseOptions) -> String {
+    let blocks = top_level_blocks(data, opts);
+    let found = blocks.iter().enumerate().find(|(_, b)| {
+        // Grab the version out of a header, IE the `foobar` of:
+        //
+        // ```markdown
+        // ## [foobar] - 2020-05-01
+        // ````
+        //
+        // This is compared to the tag passed as a function argument
+        b.version_heading(opts)
+            .is_some_and(|hdr| same_version(&hdr.version, tag))
+    });
+    let (idx, heading) = match found {
+        Some(found) => found,
+        None => return String::new(),
+    };
+
+    let end = blocks[idx + 1..]
+        .iter()
+        .find(|b| matches!(b.heading_level(), Some(level) if level <= opts.version_level))
+        .map(|b| line_offset(data, b.start))
+        .unwrap_or_else(|| data.len());
+    let start = line_offset(data, heading.body_start()).min(end);
+
+    let mut lines: Vec<&str> = data[start..end].split_inclusive('\n').collect();
+    while matches!(lines.first(), Some(l) if l.trim().is_empty()) {
+        lines.remove(0);
+    }
+    while matches!(lines.last(), Some(l) if l.trim().is_empty() || link_definition(l.trim_end()).is_some())
+    {
+        lines.pop();
+    }
+
+    let mut notes = lines.concat();
+    let lower = notes.to_lowercase();
+    let mut used: Vec<(String, String)> = link_definitions(&data.lines().collect::<Vec<_>>())
+        .into_iter()
+        .filter(|(label, _)| {
+            lower.contains(&format!("[{}]", label)) && !lower.contains(&format!("[{}]:", label))
+        })
+        .collect();
+    used.sort();
+
+    if !used.is_empty() {
+        if !notes.ends_with('\n') {
+            notes.push('\n');
+        }
+        notes.push('\n');
+        for (label, url) in used {
+            notes.push_str(&format!("[{}]: {}\n", label, url));
+        }
+    }
+
+    notes
+}
+
+/// Reads the release notes for a tag like [read](fn.read.html), but fails with a
+/// descriptive error when the changelog has no entry for the tag or the entry
+/// is empty. Set `allow_empty` to get an empty string back instead.
+pub(crate) fn release_notes<T, U>(
+    fname: T,
+    tag: U,
+    allow_empty: bool,
+    opts: &ParseOptions,
+) -> Result<String>
+where
+    T: Into<PathBuf>,
+    U: Into<String>,
+{
+    let fname = fname.into();
+    let tag = tag.into();
+    let changelog = Changelog::read(&fname, opts)?;
+
+    // an entry with nothing but empty section headings is still empty
+    match changelog.release(&tag) {
+        Some(entry) if allow_empty || !entry.is_empty() => read(&fname, &tag, opts),
+        None if allow_empty => Ok(String::new()),
+        Some(_) => Err(anyhow!(
+            "the entry for version {} in {} is empty, add release notes or pass --allow-empty-notes",
+            tag,
+            fname.display(),
+        )),
+        None => {
+            let found: Vec<&str> = changelog
+                .releases
+                .iter()
+                .map(|r| r.version.as_str())
+                .collect();
+
+            Err(anyhow!(
+                "{} has no entry for version {} (found: {}), check the version headings or pass --allow-empty-notes",
+                fname.display(),
+                tag,
+                match found.len() {
+                    0 => "no versions".to_string(),
+                    _ => found.join(", "),
+                },
+            ))
+        }
+    }
+}
+
+/// Reads the release notes for every version in the changelog that is newer
+/// than `since` (the latest release) and no newer than `version`, for when
+/// versions were skipped or several were added since the last release. Each
+/// version's notes go under a heading for that version, newest first. If there
+/// is only one such version, its notes are returned like [read](fn.read.html)
+/// does.
+pub(crate) fn cumulative_notes<T>(
+    fname: T,
+    version: &Version,
+    since: Option<&Version>,
+    scheme: &Scheme,
+    opts: &ParseOptions,
+) -> Result<String>
+where
+    T: Into<PathBuf>,
+{
+    let data = read_to_string(fname.into())?;
+    let changelog = Changelog::parse(&data, opts)?;
+    let hashes = "#".repeat(opts.version_level as usize);
+
+    let parts: Vec<String> = changelog
+        .releases
+        .iter()
+        .filter(|r| match scheme.parse(&r.version) {
+            Ok(v) => v <= *version && since.is_none_or(|since| v > *since),
+            Err(_) => false,
+        })
+        .filter_map(|r| {
+            let notes = extract(&data, &r.version, opts);
+            if notes.trim().is_empty() {
+                return None;
+            }
+            Some(match r.date {
+                Some(date) => format!("{} {} - {}\n\n{}", hashes, r.version, date, notes),
+                None => format!("{} {}\n\n{}", hashes, r.version, notes),
+            })
+        })
+        .collect();
+
+    match parts.len() {
+        0 | 1 => Ok(extract(&data, &scheme.format(version), opts)),
+        _ => Ok(parts.join("\n")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn basic() {
+        let res = super::read("testdata/basic.md", "0.1.0", &Default::default());
+        assert!(res.is_ok());
+        let delta = res.unwrap();
+        assert_eq!(delta, "Hi there this is a test!\n### ADDED\n- something\n")
+    }
+
+    #[test]
+    fn brackets() {
+        let res = super::read("testdata/brackets.md", "0.1.0", &Default::default());
+        assert!(res.is_ok());
+        let delta = res.unwrap();
+        assert_eq!(delta, "Hi there this is a test!\n### ADDED\n- something\n")
+    }
+
+    #[test]
+    fn dated() {
+        let delta =
+            super::read("testdata/keepachangelog.md", "1.1.0", &Default::default()).unwrap();
+        assert!(delta.starts_with("This release completes our Flopnax sprint!\n"));
+
+        let delta =
+            super::read("testdata/keepachangelog.md", "1.0.0", &Default::default()).unwrap();
+        assert_eq!(delta, "### Removed\n\n- The old REST API\n");
+
+        // matched the same way as Changelog::release
+        let delta = super::read(
+            "testdata/keepachangelog.md",
+            "unreleased",
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(delta, "### Added\n\n- Support for frobnicating\n");
+        let delta =
+            super::read("testdata/keepachangelog.md", "[1.0.0]", &Default::default()).unwrap();
+        assert_eq!(delta, "### Removed\n\n- The old REST API\n");
+    }
+
+    #[test]
+    fn lossless() {
+        let delta = super::read("testdata/lossless.md", "0.2.0", &Default::default()).unwrap();
+        assert_eq!(delta, include_str!("../../testdata/lossless-0.2.0.md"));
+    }
+
+    #[test]
+    fn release_notes() {
+        let err = super::release_notes("testdata/empty.md", "0.3.0", false, &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "testdata/empty.md has no entry for version 0.3.0 (found: Unreleased, 0.2.0, 0.1.1, 0.1.0), check the version headings or pass --allow-empty-notes"
+        );
+
+        let err = super::release_notes("testdata/empty.md", "0.2.0", false, &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the entry for version 0.2.0 in testdata/empty.md is empty, add release notes or pass --allow-empty-notes"
+        );
+
+        let notes =
+            super::release_notes("testdata/empty.md", "0.2.0", true, &Default::default()).unwrap();
+        assert_eq!(notes, "");
+        let err = super::release_notes("testdata/empty.md", "0.1.1", false, &Default::default())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the entry for version 0.1.1 in testdata/empty.md is empty, add release notes or pass --allow-empty-notes"
+        );
+        let notes =
+            super::release_notes("testdata/empty.md", "0.1.0", false, &Default::default()).unwrap();
+        assert_eq!(notes, "- something\n");
+    }
+
+    #[test]
+    fn heading_pattern() {
+        let opts = super::ParseOptions {
+            version_level: 3,
+            heading_pattern: Some(r"Version {version} \({date}\)".parse().unwrap()),
+            ..Default::default()
+        };
+        let delta = super::read("testdata/headings.md", "1.1.0", &opts).unwrap();
+        assert_eq!(delta, "#### Fixed\n\n- Typos in the help text\n");
+        let delta = super::read("testdata/headings.md", "1.0.0", &opts).unwrap();
+        assert_eq!(delta, "First release.\n");
+
+        let delta = super::read("testdata/headings.md", "1.1.0", &Default::default()).unwrap();
+        assert_eq!(delta, "");
+    }
+
+    #[test]
+    fn cumulative_notes() {
+        let v = |v: &str| semver::Version::parse(v).unwrap();
+        let notes = |since: Option<&str>| {
+            let since = since.map(v);
+            super::cumulative_notes(
+                "testdata/keepachangelog.md",
+                &v("1.1.0"),
+                since.as_ref(),
+                &Default::default(),
+                &Default::default(),
+            )
+            .unwrap()
+        };
+
+        let all = notes(Some("0.1.0"));
+        assert!(all.starts_with("## 1.1.0 - 2020-05-01\n\nThis release completes"));
+        assert!(all.contains("\n## 1.0.0 - 2020-04-01\n\n### Removed\n"));
+        assert!(!all.contains("proof of concept"));
+        assert!(notes(None).ends_with("## 0.1.0\n\nFirst release, proof of concept.\n"));
+        assert_eq!(
+            notes(Some("1.0.0")),
+            super::read("testdata/keepachangelog.md", "1.1.0", &Default::default()).unwrap()
+        );
+    }
+}
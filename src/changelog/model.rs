@@ -0,0 +1,917 @@
+use crate::version::Level;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use comrak::nodes::NodeValue;
+use comrak::{parse_document, Arena, ComrakOptions};
+use regex::Regex;
+use std::{collections::HashMap, fmt, fs::read_to_string, path::PathBuf, str::FromStr};
+
+/// A typed view of a [Keep a Changelog](https://keepachangelog.com/en/1.0.0/)
+/// style changelog. This is parsed with comrak, but the text of every part is
+/// sliced out of the source file so that it stays the way the author wrote it.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Changelog {
+    /// Everything before the first version heading (the title and the blurb
+    /// about the changelog format).
+    pub preamble: String,
+    /// Release entries in the order they appear in the file, newest first by
+    /// convention. The `Unreleased` section is an entry like any other.
+    pub releases: Vec<ReleaseEntry>,
+}
+
+/// One version heading of the changelog and everything under it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReleaseEntry {
+    /// The level of the version heading, IE 2 for `## 1.0.0`. Sections are
+    /// one level below it.
+    pub level: u32,
+    /// The version as written in the heading, without brackets.
+    pub version: String,
+    /// The release date, IE the `2020-05-01` of `## [0.1.0] - 2020-05-01`.
+    pub date: Option<NaiveDate>,
+    /// The URL the version links to, either inline in the heading or from a
+    /// link reference definition at the bottom of the file.
+    pub link: Option<String>,
+    /// Set if the heading ends with `[YANKED]`, meaning the release was
+    /// pulled because of a serious problem.
+    pub yanked: bool,
+    /// Free text between the version heading and the first section heading.
+    pub description: String,
+    /// The sections one level below the version heading (`### Added`, `### Fixed`, etc.) of this entry.
+    pub sections: Vec<Section>,
+}
+
+impl Default for ReleaseEntry {
+    fn default() -> Self {
+        ReleaseEntry {
+            level: 2,
+            version: String::new(),
+            date: None,
+            link: None,
+            yanked: false,
+            description: String::new(),
+            sections: vec![],
+        }
+    }
+}
+
+/// A named group of changes in a release entry.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The name of the section as written, IE `Added` or `FIXED`.
+    pub name: String,
+    /// The list items in this section without their bullet markers. Nested
+    /// content is kept and dedented to line up with the item text.
+    pub items: Vec<String>,
+    /// Everything in this section that isn't a list, such as a paragraph or a
+    /// table, kept as written along with how many items come before it.
+    pub blocks: Vec<(usize, String)>,
+}
+
+/// The parsed text of a version heading. Given `[0.1.0](https://example.com) -
+/// 2020-05-01` this has a version of `0.1.0`, a link to `https://example.com`
+/// and a date of May 1st 2020. Bare (`0.1.0`) and bracketed (`[0.1.0]`)
+/// versions without dates or links work too.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Heading {
+    pub version: String,
+    pub link: Option<String>,
+    /// The [ISO 8601](https://en.wikipedia.org/wiki/ISO_8601) date after the
+    /// version, if there is one.
+    pub date: Option<NaiveDate>,
+    /// Set by a `[YANKED]` marker after the version and date.
+    pub yanked: bool,
+    /// Anything after the version and date that wasn't understood.
+    pub rest: String,
+}
+
+impl Heading {
+    /// Splits the text of a version heading into its parts. This never fails,
+    /// text that doesn't fit ends up in `rest`.
+    pub fn parse(text: &str) -> Heading {
+        let text = text.trim();
+
+        let (version, link, rest) = match text.find(']') {
+            Some(close) if text.starts_with('[') => {
+                let version = &text[1..close];
+                let rest = &text[close + 1..];
+
+                match rest.find(')') {
+                    Some(paren) if rest.starts_with('(') => {
+                        (version, Some(rest[1..paren].trim()), &rest[paren + 1..])
+                    }
+                    _ => (version, None, rest),
+                }
+            }
+            _ => match text.find(char::is_whitespace) {
+                Some(space) => (&text[..space], None, &text[space..]),
+                None => (text, None, ""),
+            },
+        };
+
+        let rest = rest.trim().trim_start_matches(&['-', '–', '—'][..]).trim();
+        let (date, rest) = match rest.split_whitespace().next() {
+            Some(word) => match NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+                Ok(date) => (Some(date), rest[word.len()..].trim()),
+                Err(_) => (None, rest),
+            },
+            None => (None, rest),
+        };
+        let (yanked, rest) = match rest.len().checked_sub("[YANKED]".len()) {
+            Some(i) if rest.is_char_boundary(i) && rest[i..].eq_ignore_ascii_case("[YANKED]") => {
+                (true, rest[..i].trim())
+            }
+            _ => (false, rest),
+        };
+
+        Heading {
+            version: version.trim().to_string(),
+            link: link.map(|l| l.to_string()),
+            date,
+            yanked,
+            rest: rest.to_string(),
+        }
+    }
+}
+
+/// Settings for how changelogs are parsed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseOptions {
+    /// Parse the changelog as [GitHub Flavored Markdown](https://github.github.com/gfm/)
+    /// (tables, strikethrough, task lists, autolinks and the tag filter)
+    /// instead of plain CommonMark. GitHub renders release notes as GFM, so
+    /// this is on by default.
+    pub gfm: bool,
+    /// The level of version headings, 2 for `## 1.2.0` by default. Sections
+    /// are the level below, and headings above it (such as a `## 2024` above
+    /// `### 1.2.0`) only group versions.
+    pub version_level: u32,
+    /// How version headings are written, if they don't follow Keep a
+    /// Changelog. See [HeadingPattern](struct.HeadingPattern.html).
+    pub heading_pattern: Option<HeadingPattern>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            gfm: true,
+            version_level: 2,
+            heading_pattern: None,
+        }
+    }
+}
+
+/// A regular expression for the text of version headings, with `{version}`
+/// where the version goes and optionally `{date}` for an ISO 8601 date, such
+/// as `v{version}` or `Version {version} \({date}\)`. The whole heading has to
+/// match. `Unreleased` headings are still found without matching.
+#[derive(Debug, Clone)]
+pub struct HeadingPattern {
+    pattern: String,
+    regex: Regex,
+}
+
+impl HeadingPattern {
+    /// Parses the text of a heading, returning `None` if it doesn't match.
+    pub fn parse(&self, text: &str) -> Option<Heading> {
+        let captures = self.regex.captures(text.trim())?;
+        Some(Heading {
+            version: captures.name("version")?.as_str().to_string(),
+            date: captures
+                .name("date")
+                .and_then(|d| NaiveDate::parse_from_str(d.as_str(), "%Y-%m-%d").ok()),
+            yanked: captures.name("yanked").is_some(),
+            ..Heading::default()
+        })
+    }
+
+    /// Writes a heading for `version` released on `date` by filling in the
+    /// pattern. Backslash escapes are removed, but any other regular
+    /// expression syntax is written as is.
+    pub fn render(&self, version: &str, date: NaiveDate) -> String {
+        let text = self
+            .pattern
+            .replace("{version}", version)
+            .replace("{date}", &date.to_string());
+        let mut result = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => result.extend(chars.next()),
+                c => result.push(c),
+            }
+        }
+        result
+    }
+}
+
+impl PartialEq for HeadingPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.pattern == other.pattern
+    }
+}
+
+impl FromStr for HeadingPattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.matches("{version}").count() != 1 {
+            return Err(anyhow!(
+                "the heading pattern {:?} must contain {{version}} exactly once",
+                s
+            ));
+        }
+
+        let regex = s
+            .replace("{version}", r"(?P<version>[^\s\[\]()]+)")
+            .replace("{date}", r"(?P<date>\d{4}-\d{2}-\d{2})");
+        let regex = Regex::new(&format!(r"^(?:{})(?:\s+(?P<yanked>(?i:\[yanked\])))?$", regex))
+            .map_err(|why| anyhow!("the heading pattern {:?} is invalid: {}", s, why))?;
+
+        Ok(HeadingPattern {
+            pattern: s.into(),
+            regex,
+        })
+    }
+}
+
+impl fmt::Display for HeadingPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+impl ParseOptions {
+    /// Parses a heading as a version heading, returning `None` if it isn't
+    /// one: it has to be at the version level and match the heading pattern
+    /// if there is one (or be `Unreleased`).
+    pub fn version_heading(&self, level: u32, text: &str) -> Option<Heading> {
+        if level != self.version_level {
+            return None;
+        }
+        let heading = Heading::parse(text);
+        match &self.heading_pattern {
+            None => Some(heading),
+            Some(pattern) => pattern
+                .parse(text)
+                .or_else(|| Some(heading).filter(|h| h.version.eq_ignore_ascii_case("unreleased"))),
+        }
+    }
+
+    /// The comrak options matching these settings.
+    pub fn comrak(&self) -> ComrakOptions {
+        ComrakOptions {
+            ext_strikethrough: self.gfm,
+            ext_tagfilter: self.gfm,
+            ext_table: self.gfm,
+            ext_autolink: self.gfm,
+            ext_tasklist: self.gfm,
+            ..ComrakOptions::default()
+        }
+    }
+}
+
+impl Changelog {
+    /// Parses a changelog out of a file on disk.
+    pub fn read<T>(fname: T, opts: &ParseOptions) -> Result<Changelog>
+    where
+        T: Into<PathBuf>,
+    {
+        let data = read_to_string(fname.into())?;
+        Changelog::parse(&data, opts)
+    }
+
+    /// Parses a changelog out of a string.
+    pub fn parse(data: &str, opts: &ParseOptions) -> Result<Changelog> {
+        let lines: Vec<&str> = data.lines().collect();
+        let links = link_definitions(&lines);
+        let blocks = top_level_blocks(data, opts);
+
+        let mut changelog = Changelog::default();
+        let first_release = blocks
+            .iter()
+            .position(|b| b.version_heading(opts).is_some())
+            .unwrap_or(blocks.len());
+        let preamble_end = blocks
+            .get(first_release)
+            .map(|b| b.start)
+            .unwrap_or(lines.len());
+        changelog.preamble = slice(&lines, 0, preamble_end, 0);
+
+        // The line where the description of the current entry starts, if the
+        // description hasn't been closed by a section heading yet.
+        let mut description_start: Option<usize> = None;
+        // Cleared by headings that group versions, so that whatever is under
+        // them isn't added to the entry above.
+        let mut in_release = false;
+
+        for (i, block) in blocks.iter().enumerate().skip(first_release) {
+            let end = blocks.get(i + 1).map(|b| b.start).unwrap_or(lines.len());
+
+            if let Some(level) = block.heading_level() {
+                if level <= opts.version_level + 1 {
+                    if let (Some(start), Some(entry)) =
+                        (description_start.take(), changelog.releases.last_mut())
+                    {
+                        entry.description = slice(&lines, start, block.start, 0);
+                    }
+                }
+            }
+
+            if let Some(heading) = block.version_heading(opts) {
+                let Heading {
+                    version,
+                    link,
+                    date,
+                    yanked,
+                    ..
+                } = heading;
+                let link = link.or_else(|| links.get(&version.to_lowercase()).cloned());
+
+                changelog.releases.push(ReleaseEntry {
+                    level: opts.version_level,
+                    version,
+                    date,
+                    link,
+                    yanked,
+                    ..ReleaseEntry::default()
+                });
+                description_start = Some(block.body_start());
+                in_release = true;
+                continue;
+            }
+
+            match &block.kind {
+                BlockKind::Heading { level, .. } if *level <= opts.version_level => {
+                    in_release = false;
+                }
+                _ if !in_release => {}
+                BlockKind::Heading { level, text, .. } if *level == opts.version_level + 1 => {
+                    if let Some(entry) = changelog.releases.last_mut() {
+                        entry.sections.push(Section {
+                            name: text.trim().to_string(),
+                            ..Section::default()
+                        });
+                    }
+                }
+                BlockKind::List(items) if description_start.is_none() => {
+                    if let Some(section) = current_section(&mut changelog) {
+                        for (j, (start, offset)) in items.iter().enumerate() {
+                            let item_end = items.get(j + 1).map(|it| it.0).unwrap_or(end);
+                            section.items.push(slice(&lines, *start, item_end, *offset));
+                        }
+                    }
+                }
+                _ if description_start.is_none() => {
+                    if let Some(section) = current_section(&mut changelog) {
+                        let text = slice(&lines, block.start, end, 0);
+                        if !text.is_empty() {
+                            section.blocks.push((section.items.len(), text));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let (Some(start), Some(entry)) = (description_start, changelog.releases.last_mut()) {
+            entry.description = slice(&lines, start, lines.len(), 0);
+        }
+
+        Ok(changelog)
+    }
+
+    /// Finds the entry for a given version. Matching ignores surrounding
+    /// brackets and case, so `unreleased` finds `## [Unreleased]`.
+    pub fn release<T>(&self, version: T) -> Option<&ReleaseEntry>
+    where
+        T: AsRef<str>,
+    {
+        self.releases
+            .iter()
+            .find(|r| same_version(&r.version, version.as_ref()))
+    }
+
+    /// Finds the `Unreleased` entry, if the changelog has one.
+    pub fn unreleased(&self) -> Option<&ReleaseEntry> {
+        self.releases.iter().find(|r| r.is_unreleased())
+    }
+}
+
+impl ReleaseEntry {
+    /// Returns true if this is the `Unreleased` section at the top of the
+    /// changelog.
+    pub fn is_unreleased(&self) -> bool {
+        self.version.eq_ignore_ascii_case("unreleased")
+    }
+
+    /// Finds a section by name, ignoring case.
+    pub fn section<T>(&self, name: T) -> Option<&Section>
+    where
+        T: AsRef<str>,
+    {
+        self.sections
+            .iter()
+            .find(|s| s.name.eq_ignore_ascii_case(name.as_ref()))
+    }
+
+    /// Returns true if there is nothing in this entry but empty section
+    /// headings.
+    pub fn is_empty(&self) -> bool {
+        self.description.trim().is_empty() && self.sections.iter().all(Section::is_empty)
+    }
+
+    /// Renders the description and sections of this entry without its
+    /// version heading, such as for release notes.
+    pub fn notes(&self) -> String {
+        let mut parts = vec![];
+        if !self.description.is_empty() {
+            parts.push(format!("{}\n", self.description));
+        }
+        parts.extend(self.sections.iter().map(|s| s.render(self.level + 1)));
+        parts.join("\n")
+    }
+
+    /// Works out the smallest semantic version bump these changes need, along
+    /// with the reason: a `Removed` section or anything marked `BREAKING`
+    /// needs a major release, `Added`, `Changed` or `Deprecated` sections need
+    /// a minor release and anything else (such as `Fixed` or `Security`) only
+    /// needs a patch release. Returns `None` if the entry is empty.
+    pub fn change_level(&self) -> Option<(Level, String)> {
+        let breaking = self.description.contains("BREAKING")
+            || self.sections.iter().any(|s| {
+                s.name.contains("BREAKING")
+                    || s.items.iter().any(|i| i.contains("BREAKING"))
+                    || s.blocks.iter().any(|(_, b)| b.contains("BREAKING"))
+            });
+        if breaking {
+            return Some((Level::Major, "a BREAKING change".into()));
+        }
+
+        let has = |name: &str| self.section(name).is_some_and(|s| !s.is_empty());
+        for (name, level) in &[
+            ("Removed", Level::Major),
+            ("Added", Level::Minor),
+            ("Changed", Level::Minor),
+            ("Deprecated", Level::Minor),
+        ] {
+            if has(name) {
+                return Some((*level, format!("changes under {}", name)));
+            }
+        }
+
+        match self.sections.iter().find(|s| !s.is_empty()) {
+            Some(s) => Some((Level::Patch, format!("changes under {}", s.name))),
+            None if !self.description.trim().is_empty() => {
+                Some((Level::Patch, "a description".into()))
+            }
+            None => None,
+        }
+    }
+}
+
+/// Renders the changelog back into markdown. Links are written as reference
+/// definitions at the bottom of the file like Keep a Changelog suggests.
+impl fmt::Display for Changelog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.preamble.is_empty() {
+            writeln!(f, "{}", self.preamble)?;
+        }
+
+        for entry in &self.releases {
+            writeln!(f)?;
+            write!(f, "{}", entry)?;
+        }
+
+        let links: Vec<&ReleaseEntry> = self.releases.iter().filter(|r| r.link.is_some()).collect();
+        if !links.is_empty() {
+            writeln!(f)?;
+        }
+        for entry in links {
+            writeln!(f, "[{}]: {}", entry.version, entry.link.as_ref().unwrap())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReleaseEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hashes = "#".repeat(self.level as usize);
+        match self.link {
+            Some(_) => write!(f, "{} [{}]", hashes, self.version)?,
+            None => write!(f, "{} {}", hashes, self.version)?,
+        }
+        if let Some(date) = &self.date {
+            write!(f, " - {}", date)?;
+        }
+        match self.yanked {
+            true => writeln!(f, " [YANKED]")?,
+            false => writeln!(f)?,
+        }
+
+        if !self.description.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "{}", self.description)?;
+        }
+
+        for section in &self.sections {
+            writeln!(f)?;
+            write!(f, "{}", section.render(self.level + 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Section {
+    /// Checks if there is nothing under the section heading.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.blocks.is_empty()
+    }
+
+    /// Renders the section as markdown under a heading at `level`, IE 3 for
+    /// `### Added`. Blocks that aren't list items are written as they were.
+    pub fn render(&self, level: u32) -> String {
+        let mut result = format!("{} {}\n", "#".repeat(level as usize), self.name);
+        let mut blocks = self.blocks.iter().peekable();
+
+        for (n, item) in self.items.iter().enumerate() {
+            let mut separate = n == 0;
+            while let Some((_, text)) = blocks.next_if(|(at, _)| *at == n) {
+                result.push_str(&format!("\n{}\n", text));
+                separate = true;
+            }
+            if separate {
+                result.push('\n');
+            }
+
+            for (i, line) in item.lines().enumerate() {
+                match (i, line.is_empty()) {
+                    (0, _) => result.push_str(&format!("- {}\n", line)),
+                    (_, true) => result.push('\n'),
+                    (_, false) => result.push_str(&format!("  {}\n", line)),
+                }
+            }
+        }
+        for (_, text) in blocks {
+            result.push_str(&format!("\n{}\n", text));
+        }
+
+        result
+    }
+}
+
+/// A top level markdown block and the line (zero-indexed) it starts on.
+pub(crate) struct Block {
+    pub(crate) start: usize,
+    pub(crate) kind: BlockKind,
+}
+
+pub(crate) enum BlockKind {
+    Heading {
+        level: u32,
+        text: String,
+        setext: bool,
+    },
+    /// The start line and content column of every item in the list.
+    List(Vec<(usize, usize)>),
+    Other,
+}
+
+impl Block {
+    pub(crate) fn heading_level(&self) -> Option<u32> {
+        match self.kind {
+            BlockKind::Heading { level, .. } => Some(level),
+            _ => None,
+        }
+    }
+
+    /// Parses this block as a version heading, see
+    /// [ParseOptions::version_heading](struct.ParseOptions.html#method.version_heading).
+    pub(crate) fn version_heading(&self, opts: &ParseOptions) -> Option<Heading> {
+        match &self.kind {
+            BlockKind::Heading { level, text, .. } => opts.version_heading(*level, text),
+            _ => None,
+        }
+    }
+
+    /// The first line after this block's heading, setext headings take up an
+    /// extra line for the underline.
+    pub(crate) fn body_start(&self) -> usize {
+        match self.kind {
+            BlockKind::Heading { setext: true, .. } => self.start + 2,
+            _ => self.start + 1,
+        }
+    }
+}
+
+pub(crate) fn top_level_blocks(data: &str, opts: &ParseOptions) -> Vec<Block> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, data, &opts.comrak());
+
+    root.children()
+        .map(|node| {
+            let nd = node.data.borrow();
+            let mut start = (nd.start_line as usize).saturating_sub(1);
+            let kind = match nd.value {
+                NodeValue::Heading(ref hdr) => BlockKind::Heading {
+                    level: hdr.level,
+                    text: String::from_utf8_lossy(&nd.content).trim().to_string(),
+                    setext: hdr.setext,
+                },
+                NodeValue::List(_) => BlockKind::List(
+                    node.children()
+                        .filter_map(|item| {
+                            let it = item.data.borrow();
+                            match it.value {
+                                NodeValue::Item(ref list) => Some((
+                                    (it.start_line as usize).saturating_sub(1),
+                                    list.marker_offset + list.padding,
+                                )),
+                                _ => None,
+                            }
+                        })
+                        .collect(),
+                ),
+                // comrak only notices a table when it gets to the delimiter
+                // row, so its start line is one past the header row
+                NodeValue::Table(_) => {
+                    start = start.saturating_sub(1);
+                    BlockKind::Other
+                }
+                _ => BlockKind::Other,
+            };
+
+            Block { start, kind }
+        })
+        .collect()
+}
+
+/// Parses a link reference definition such as `[0.1.0]: https://example.com`.
+pub(crate) fn link_definition(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start_matches(' ');
+    if line.len() - trimmed.len() > 3 || !trimmed.starts_with('[') {
+        return None;
+    }
+
+    let line = &trimmed[1..];
+    let close = line.find("]:")?;
+    let url = line[close + 2..].split_whitespace().next()?;
+
+    Some((&line[..close], url))
+}
+
+/// Collects every link reference definition in the file, keyed by lowercase
+/// label.
+pub(crate) fn link_definitions(lines: &[&str]) -> HashMap<String, String> {
+    lines
+        .iter()
+        .filter_map(|l| link_definition(l))
+        .map(|(label, url)| (label.to_lowercase(), url.to_string()))
+        .collect()
+}
+
+/// Checks if a version from a heading is the one asked for. Matching ignores
+/// surrounding brackets and case, so `[unreleased]` is `Unreleased`.
+pub(crate) fn same_version(heading: &str, version: &str) -> bool {
+    let version = version.trim_start_matches('[').trim_end_matches(']');
+    heading.eq_ignore_ascii_case(version)
+}
+
+/// Returns the byte offset of the start of line `n` (zero-indexed), or the
+/// length of the data if there aren't that many lines.
+pub(crate) fn line_offset(data: &str, n: usize) -> usize {
+    match n {
+        0 => 0,
+        _ => data
+            .match_indices('\n')
+            .nth(n - 1)
+            .map(|(i, _)| i + 1)
+            .unwrap_or_else(|| data.len()),
+    }
+}
+
+/// Returns the most recently started section of the most recent entry.
+fn current_section(changelog: &mut Changelog) -> Option<&mut Section> {
+    changelog.releases.last_mut()?.sections.last_mut()
+}
+
+/// Joins lines `start..end` back together, leaving out link reference
+/// definitions and surrounding blank lines. The first line has `offset`
+/// characters cut off (the list marker) and later lines are dedented by up to
+/// `offset` spaces.
+fn slice(lines: &[&str], start: usize, end: usize, offset: usize) -> String {
+    let end = end.min(lines.len());
+    if start >= end {
+        return String::new();
+    }
+
+    let kept: Vec<&str> = lines[start..end]
+        .iter()
+        .enumerate()
+        .filter(|(_, l)| link_definition(l).is_none())
+        .map(|(i, l)| {
+            if i == 0 {
+                l.get(offset..).unwrap_or("")
+            } else {
+                let indent = l.len() - l.trim_start_matches(' ').len();
+                &l[indent.min(offset)..]
+            }
+        })
+        .collect();
+
+    kept.join("\n").trim_matches('\n').trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let cl = Changelog::read("testdata/keepachangelog.md", &Default::default()).unwrap();
+        assert!(cl.preamble.starts_with("# Changelog\n"));
+        assert!(cl
+            .preamble
+            .ends_with("Semantic Versioning](https://semver.org/spec/v2.0.0.html)."));
+
+        let versions: Vec<&str> = cl.releases.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(versions, vec!["Unreleased", "1.1.0", "1.0.0", "0.1.0"]);
+
+        let unreleased = cl.unreleased().unwrap();
+        assert_eq!(
+            unreleased.link.as_deref(),
+            Some("https://github.com/yolo/swag/compare/v1.1.0...HEAD")
+        );
+        assert_eq!(
+            unreleased.sections[0].items,
+            vec!["Support for frobnicating"]
+        );
+
+        let entry = cl.release("1.1.0").unwrap();
+        assert_eq!(
+            entry.date,
+            Some(NaiveDate::from_ymd_opt(2020, 5, 1).unwrap())
+        );
+        assert_eq!(
+            entry.description,
+            "This release completes our Flopnax sprint!"
+        );
+        assert_eq!(
+            entry.section("fixed").unwrap().items,
+            vec![
+                "Solved WAT-2392 which previously prevented users from being able to\nrefrobnicate already frobnicated strings.",
+                "Nested content survives:\n- one\n- two",
+            ]
+        );
+
+        let entry = cl.release("[1.0.0]").unwrap();
+        assert_eq!(entry.link.as_deref(), Some("https://example.com/1.0.0"));
+        assert_eq!(
+            entry.date,
+            Some(NaiveDate::from_ymd_opt(2020, 4, 1).unwrap())
+        );
+
+        let entry = cl.release("0.1.0").unwrap();
+        assert_eq!(entry.link, None);
+        assert_eq!(entry.date, None);
+        assert_eq!(entry.description, "First release, proof of concept.");
+        assert!(entry.sections.is_empty());
+    }
+
+    #[test]
+    fn change_level() {
+        let cl = Changelog::read("testdata/keepachangelog.md", &Default::default()).unwrap();
+        let level = |version: &str| cl.release(version).unwrap().change_level();
+
+        assert_eq!(
+            level("Unreleased"),
+            Some((Level::Minor, "changes under Added".into()))
+        );
+        assert_eq!(
+            level("1.0.0"),
+            Some((Level::Major, "changes under Removed".into()))
+        );
+        assert_eq!(level("0.1.0"), Some((Level::Patch, "a description".into())));
+
+        let cl = Changelog::parse(
+            "## 2.0.0\n\n### Fixed\n\n- **BREAKING:** stop frobnicating\n\n## 1.0.1\n\n### Security\n\n- patched\n\n## 1.0.0\n",
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            cl.releases[0].change_level(),
+            Some((Level::Major, "a BREAKING change".into()))
+        );
+        assert_eq!(
+            cl.releases[1].change_level(),
+            Some((Level::Patch, "changes under Security".into()))
+        );
+        assert_eq!(cl.releases[2].change_level(), None);
+    }
+
+    #[test]
+    fn heading() {
+        let hdr = Heading::parse("[1.2.0](https://example.com/1.2.0) - 2024-05-01");
+        assert_eq!(hdr.version, "1.2.0");
+        assert_eq!(hdr.link.as_deref(), Some("https://example.com/1.2.0"));
+        assert_eq!(hdr.date, Some(NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()));
+        assert_eq!(hdr.rest, "");
+
+        let hdr = Heading::parse("1.2.0 - someday");
+        assert_eq!(hdr.version, "1.2.0");
+        assert_eq!(hdr.date, None);
+        assert_eq!(hdr.rest, "someday");
+
+        assert_eq!(Heading::parse("[Unreleased]").version, "Unreleased");
+
+        let hdr = Heading::parse("[1.3.0] - 2024-02-01 [YANKED]");
+        assert_eq!(hdr.version, "1.3.0");
+        assert!(hdr.yanked);
+        assert_eq!(hdr.rest, "");
+        assert!(Heading::parse("1.3.0 [yanked]").yanked);
+    }
+
+    #[test]
+    fn heading_pattern() {
+        let pattern: HeadingPattern = r"Version {version} \({date}\)".parse().unwrap();
+        let hdr = pattern.parse("Version 1.2.0 (2024-05-01)").unwrap();
+        assert_eq!(hdr.version, "1.2.0");
+        assert_eq!(hdr.date, NaiveDate::from_ymd_opt(2024, 5, 1));
+        assert_eq!(pattern.parse("1.2.0 - 2024-05-01"), None);
+        assert!(
+            pattern
+                .parse("Version 1.2.0 (2024-05-01) [YANKED]")
+                .unwrap()
+                .yanked
+        );
+        let date = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+        assert_eq!(pattern.render("1.3.0", date), "Version 1.3.0 (2024-06-01)");
+        assert!("v{version} {version}".parse::<HeadingPattern>().is_err());
+        assert!("v{version} (".parse::<HeadingPattern>().is_err());
+
+        let opts = ParseOptions {
+            version_level: 3,
+            heading_pattern: Some(pattern),
+            ..ParseOptions::default()
+        };
+        let cl = Changelog::read("testdata/headings.md", &opts).unwrap();
+        let versions: Vec<&str> = cl.releases.iter().map(|r| r.version.as_str()).collect();
+        assert_eq!(versions, vec!["Unreleased", "1.2.0", "1.1.0", "1.0.0"]);
+        assert_eq!(cl.preamble, "# Changelog");
+        assert_eq!(
+            cl.releases[2].sections[0].items,
+            vec!["Typos in the help text"]
+        );
+        assert_eq!(cl.releases[3].description, "First release.");
+    }
+
+    #[test]
+    fn gfm() {
+        let table = "# Changelog\n\n## 0.1.0\n\n### Added\n\n- [x] frobnicate\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- ~~strike~~\n";
+
+        let opts = ParseOptions::default();
+        assert!(opts.comrak().ext_table && opts.comrak().ext_tasklist);
+        let cl = Changelog::parse(table, &opts).unwrap();
+        let added = &cl.releases[0].sections[0];
+        assert_eq!(added.items, vec!["[x] frobnicate", "~~strike~~"]);
+        assert_eq!(
+            added.blocks,
+            vec![(1, "| a | b |\n|---|---|\n| 1 | 2 |".to_string())]
+        );
+        assert_eq!(
+            added.render(3),
+            "### Added\n\n- [x] frobnicate\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n- ~~strike~~\n"
+        );
+
+        let opts = ParseOptions {
+            gfm: false,
+            ..ParseOptions::default()
+        };
+        assert!(!opts.comrak().ext_table && !opts.comrak().ext_tasklist);
+    }
+
+    #[test]
+    fn yanked() {
+        let cl = Changelog::read("testdata/yanked.md", &Default::default()).unwrap();
+        let yanked: Vec<bool> = cl.releases.iter().map(|r| r.yanked).collect();
+        assert_eq!(yanked, vec![false, false, true]);
+        assert!(cl
+            .to_string()
+            .contains("\n## 1.3.0 - 2024-02-01 [YANKED]\n"));
+    }
+
+    #[test]
+    fn render_round_trip() {
+        let cl = Changelog::read("testdata/keepachangelog.md", &Default::default()).unwrap();
+        let rendered = cl.to_string();
+        assert!(rendered.contains("\n## [1.1.0] - 2020-05-01\n"));
+        assert!(rendered.contains("\n## 0.1.0\n"));
+        assert!(rendered.ends_with("[1.0.0]: https://example.com/1.0.0\n"));
+        assert_eq!(
+            Changelog::parse(&rendered, &Default::default()).unwrap(),
+            cl
+        );
+    }
+}
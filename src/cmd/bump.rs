@@ -0,0 +1,150 @@
+use crate::{
+    changelog,
+    cmd::{ChangelogOptions, TagOptions, VersionOptions},
+    version::{Level, Scheme},
+};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use std::{fs, path::PathBuf, str::FromStr};
+
+/// How far to bump the version: a fixed level, or `auto` to work it out from
+/// the `Unreleased` section of the changelog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    Auto,
+    Level(Level),
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "auto" => Ok(Target::Auto),
+            _ => s.parse().map(Target::Level).map_err(|_| {
+                anyhow!(
+                    "unknown bump level {:?}, expected auto, major, minor, patch, premajor, preminor, prepatch or prerelease",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+/// Bumps the version by `target` in the version source and targets,
+/// optionally adding a heading for the new version to the changelog, with
+/// compare links to tags named by `tags`.
+pub async fn run(
+    target: Target,
+    id: Option<String>,
+    update_changelog: bool,
+    fname: PathBuf,
+    tags: TagOptions,
+    format: ChangelogOptions,
+    options: VersionOptions,
+) -> Result<()> {
+    let scheme = options.scheme.versioning;
+    let today = Utc::now().naive_utc().date();
+    let current = options.read()?;
+    let level = match target {
+        Target::Level(level) => level,
+        Target::Auto => {
+            let (level, reason) = auto_level(&fname, &format, scheme, current.major)?;
+            println!("picked a {} bump for {}", level, reason);
+            level
+        }
+    };
+    let next = scheme.bump(&current, level, id.as_deref(), today)?;
+
+    if update_changelog {
+        let data = fs::read_to_string(&fname)?;
+        let data = changelog::edit::insert_heading(
+            &data,
+            &scheme.format(&next),
+            today,
+            &(&format).into(),
+            &tags.template(scheme)?,
+        )?;
+        fs::write(&fname, data)?;
+    }
+    options.write(&next)?;
+
+    println!(
+        "bumped version from {} to {}",
+        scheme.format(&current),
+        scheme.format(&next)
+    );
+
+    Ok(())
+}
+
+/// Works out the bump level from what is in the `Unreleased` section of the
+/// changelog, see [ReleaseEntry::change_level](../../changelog/struct.ReleaseEntry.html#method.change_level).
+/// Versions below 1.0.0 are bumped one level lower, and calendar versions
+/// always get a patch bump since the level doesn't change them.
+fn auto_level(
+    fname: &PathBuf,
+    format: &ChangelogOptions,
+    scheme: Scheme,
+    major: u64,
+) -> Result<(Level, String)> {
+    let cl = changelog::Changelog::read(fname, &format.into())?;
+    let (level, reason) = cl
+        .unreleased()
+        .and_then(|e| e.change_level())
+        .ok_or_else(|| {
+            anyhow!(
+                "the Unreleased section of {} is empty, there is nothing to pick a bump level from",
+                fname.display()
+            )
+        })?;
+
+    let level = match (scheme, major, level) {
+        (Scheme::CalVer(_), _, _) => Level::Patch,
+        (_, 0, Level::Major) => Level::Minor,
+        (_, 0, _) => Level::Patch,
+        (_, _, level) => level,
+    };
+    Ok((level, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Target;
+    use crate::version::{Level, Scheme};
+    use std::path::PathBuf;
+
+    #[test]
+    fn auto_level() {
+        let fname = PathBuf::from("testdata/keepachangelog.md");
+        let level = |scheme: &str, major| {
+            let scheme: Scheme = scheme.parse().unwrap();
+            super::auto_level(&fname, &Default::default(), scheme, major).unwrap()
+        };
+
+        assert_eq!(
+            level("semver", 1),
+            (Level::Minor, "changes under Added".into())
+        );
+        assert_eq!(level("semver", 0).0, Level::Patch);
+        assert_eq!(level("YYYY.0M.MICRO", 2024).0, Level::Patch);
+
+        let err = super::auto_level(
+            &PathBuf::from("testdata/empty.md"),
+            &Default::default(),
+            Scheme::SemVer,
+            1,
+        )
+        .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("there is nothing to pick a bump level from"));
+
+        assert_eq!("auto".parse::<Target>().unwrap(), Target::Auto);
+        assert_eq!(
+            "minor".parse::<Target>().unwrap(),
+            Target::Level(Level::Minor)
+        );
+        assert!("huge".parse::<Target>().is_err());
+    }
+}
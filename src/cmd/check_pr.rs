@@ -0,0 +1,260 @@
+use crate::{
+    changelog::{fragments, Changelog, ParseOptions},
+    cmd::{ChangelogOptions, CheckPrOptions, VersionOptions},
+    git, version,
+};
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// A pattern for paths relative to the root of the repo, like the ones in
+/// `.gitignore`: `*` matches anything but `/`, `**` matches anything and a
+/// trailing `/` matches everything in a directory. Patterns with a `/` start
+/// at the root of the repo, ones without match files with that name in any
+/// directory, such as `*.md`.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    pattern: String,
+    regex: Regex,
+}
+
+impl Glob {
+    pub fn is_match(&self, path: &Path) -> bool {
+        path.to_str().is_some_and(|path| self.regex.is_match(path))
+    }
+}
+
+impl FromStr for Glob {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let pattern = s.trim_start_matches("./").trim_start_matches('/');
+        if pattern.is_empty() {
+            return Err(anyhow!("{:?} is not a valid path pattern", s));
+        }
+
+        let mut regex = String::from("^");
+        if !pattern.contains('/') {
+            regex.push_str("(?:.*/)?");
+        }
+        let mut chars = pattern.trim_end_matches('/').chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        regex.push_str("(?:.*/)?");
+                    } else {
+                        regex.push_str(".*");
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push_str(if pattern.ends_with('/') {
+            "/.*$"
+        } else {
+            "(?:/.*)?$"
+        });
+
+        Ok(Glob {
+            pattern: s.into(),
+            regex: Regex::new(&regex)?,
+        })
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
+/// Checks that a pull request from `HEAD` into `opts.base` keeps the changelog
+/// in step with the code: if any file that isn't ignored changed, so must the
+/// Unreleased section (or a fragment must be added), and if the version
+/// changed, the changelog needs an entry for the new version. This runs from
+/// the root of the repo.
+pub async fn run(
+    changelog: PathBuf,
+    opts: CheckPrOptions,
+    format: ChangelogOptions,
+    version: VersionOptions,
+) -> Result<()> {
+    let repo = git2::Repository::open(".")?;
+    let format: ParseOptions = (&format).into();
+    let changelog = changelog.strip_prefix(".").unwrap_or(&changelog);
+
+    if opts.label.contains(&opts.skip_label) {
+        println!(
+            "skipping the changelog checks, the pull request is labeled {}",
+            opts.skip_label
+        );
+        return Ok(());
+    }
+    let commits = git::commits_since(&repo, Some(&opts.base))?;
+    if let Some((id, _)) = commits
+        .iter()
+        .find(|(_, message)| has_trailer(message, &opts.skip_trailer))
+    {
+        println!(
+            "skipping the changelog checks, commit {} has a {} trailer",
+            id, opts.skip_trailer
+        );
+        return Ok(());
+    }
+
+    let merge_base = git::merge_base(&repo, &opts.base)?;
+    let changed = git::changed_files(&repo, &opts.base)?;
+    let current = Changelog::read(changelog, &format)?;
+    let before = match git::read_file(&repo, &merge_base, changelog)? {
+        Some(data) => Some(Changelog::parse(&data, &format)?),
+        None => None,
+    };
+    let mut problems = vec![];
+
+    let fragments_dir = opts
+        .fragments
+        .as_ref()
+        .map(|dir| dir.strip_prefix(".").unwrap_or(dir));
+    let in_fragments = |path: &Path| fragments_dir.is_some_and(|dir| path.starts_with(dir));
+    let sources: Vec<&PathBuf> = changed
+        .iter()
+        .map(|(_, path)| path)
+        .filter(|path| *path != changelog && !in_fragments(path))
+        .filter(|path| !opts.ignore.iter().any(|glob| glob.is_match(path)))
+        .collect();
+
+    if let Some(first) = sources.first() {
+        let unreleased = |cl: &Changelog| cl.unreleased().map(|entry| entry.to_string());
+        let mut fragment = None;
+        for (status, path) in &changed {
+            if in_fragments(path)
+                && *status != git2::Delta::Deleted
+                && fragments::Fragment::read(path)?.is_some()
+            {
+                fragment = Some(path);
+                break;
+            }
+        }
+
+        if unreleased(&current) != before.as_ref().and_then(unreleased) {
+            println!("the Unreleased section of {} changed", changelog.display());
+        } else if let Some(path) = fragment {
+            println!("found changelog fragment {}", path.display());
+        } else {
+            let fragment_hint = match fragments_dir {
+                Some(dir) => format!(
+                    " or in a fragment such as {}",
+                    dir.join("1234.fixed.md").display()
+                ),
+                None => String::new(),
+            };
+            problems.push(format!(
+                "{} changed since {} but the Unreleased section of {} didn't, describe the changes there{} (changes that don't need an entry can skip this check with the {} label or a `{}: true` trailer)",
+                match sources.len() {
+                    1 => first.display().to_string(),
+                    n => format!("{} and {} other files", first.display(), n - 1),
+                },
+                opts.base,
+                changelog.display(),
+                fragment_hint,
+                opts.skip_label,
+                opts.skip_trailer
+            ));
+        }
+    }
+
+    let source = version::Source::from(&version);
+    let version_file = source.path.strip_prefix(".").unwrap_or(&source.path);
+    if changed.iter().any(|(_, path)| path == version_file) {
+        let scheme = &version.scheme.versioning;
+        let next = source.read(scheme)?;
+        let previous = git::read_file(&repo, &merge_base, version_file)?
+            .and_then(|data| source.parse(&data, scheme).ok());
+
+        if previous.as_ref() != Some(&next) {
+            let tag = scheme.format(&next);
+            match current.release(&tag) {
+                Some(_) => println!("found the changelog entry for version {}", tag),
+                None => problems.push(format!(
+                    "{} changed the version to {} but {} has no entry for it",
+                    version_file.display(),
+                    tag,
+                    changelog.display()
+                )),
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(anyhow!("{}", problems.join("\n")));
+    }
+    Ok(())
+}
+
+/// Checks if a commit message has a trailer named `name` (in any case), such
+/// as `Skip-Changelog: true`. A value of `false` or `no` doesn't count.
+fn has_trailer(message: &str, name: &str) -> bool {
+    message.lines().any(|line| match line.split_once(':') {
+        Some((key, value)) => {
+            let value = value.trim().to_lowercase();
+            key.trim().eq_ignore_ascii_case(name) && value != "false" && value != "no"
+        }
+        None => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Glob;
+    use std::path::Path;
+
+    #[test]
+    fn glob() {
+        let cases = vec![
+            ("docs/", "docs/usage.md", true),
+            ("docs/", "src/docs/usage.md", false),
+            ("docs", "docs/usage.md", true),
+            ("*.md", "README.md", true),
+            ("*.md", "doc/usage.md", true),
+            ("*.md", "src/main.rs", false),
+            ("src/**/*.snap", "src/a/b/c.snap", true),
+            ("src/**/*.snap", "src/c.snap", true),
+            ("src/*.snap", "src/a/c.snap", false),
+            (".github/**", ".github/workflows/ci.yml", true),
+            ("./Cargo.lock", "Cargo.lock", true),
+            ("Cargo.lock", "Cargo.lock.bak", false),
+            ("VERSIO?", "VERSION", true),
+        ];
+        for (pattern, path, expected) in cases {
+            let glob: Glob = pattern.parse().unwrap();
+            assert_eq!(
+                glob.is_match(Path::new(path)),
+                expected,
+                "{} {}",
+                pattern,
+                path
+            );
+        }
+        assert!("./".parse::<Glob>().is_err());
+    }
+
+    #[test]
+    fn has_trailer() {
+        let message = "docs: fix a typo\n\nskip-changelog: true\n";
+        assert!(super::has_trailer(message, "Skip-Changelog"));
+        assert!(!super::has_trailer(message, "Co-authored-by"));
+        assert!(!super::has_trailer(
+            "fix: it\n\nSkip-Changelog: no\n",
+            "Skip-Changelog"
+        ));
+    }
+}
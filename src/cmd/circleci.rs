@@ -8,9 +8,10 @@ pub async fn run(ccie: CircleCIEnv) -> Result<()> {
     match ccie.branch.as_str() {
         "master" => {
             let changelog_fname = ccie.changelog_fname.clone();
+            let release = ccie.release.clone();
             let common: Common = ccie.into();
 
-            cmd::cut::run(common, changelog_fname).await
+            cmd::cut::run(common, changelog_fname, release).await
         }
         _ => {
             println!("don't need to run on {}", ccie.branch);
@@ -1,33 +1,339 @@
-use crate::{git, changelog, version, cmd::*};
+use crate::{git, changelog::{self, autolink::Autolinker}, version::{self, Scheme}, cmd::*};
 use github::*;
-use anyhow::Result;
-use std::path::PathBuf;
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, Utc};
+use semver::Version;
+use std::{path::{Path, PathBuf}, str::FromStr};
+
+/// Where the release notes come from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NotesSource {
+    /// The entry for the version in the changelog
+    #[default]
+    Changelog,
+    /// Conventional Commits since the latest release
+    Commits,
+    /// The changelog if it has a non-empty entry for the version, commits if
+    /// it doesn't
+    Auto,
+}
+
+impl FromStr for NotesSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "changelog" => Ok(NotesSource::Changelog),
+            "commits" => Ok(NotesSource::Commits),
+            "auto" => Ok(NotesSource::Auto),
+            _ => Err(anyhow!(
+                "unknown notes source {:?}, expected changelog, commits or auto",
+                s
+            )),
+        }
+    }
+}
 
 /// Cuts a new release with GitHub details and a changelog filename.
-pub async fn run(common: Common, fname: PathBuf) -> Result<()> {
+pub async fn run(common: Common, fname: PathBuf, opts: ReleaseOptions) -> Result<()> {
     let repo = git2::Repository::open(".")?;
-    let tag = version::read_version("VERSION")?;
-    let vtag = format!("v{}", tag);
-    let desc = changelog::read(fname, &tag)?;
+    let scheme = opts.version.scheme.versioning;
+    let release_version = opts.version.read()?;
+    let tag = scheme.format(&release_version);
+    let template = opts.tag.parse(Some(&common.name), scheme)?;
+    let vtag = template.format(&release_version);
+    let format = (&opts.format).into();
+    let today = Utc::now().naive_utc().date();
 
-    if git::has_tag(&repo, &vtag)? || git::has_tag(&repo, &tag)? {
-        /* the tag exists in the repo */
-        println!("{} already exists as a git tag, exiting", vtag);
-        return Ok(());
+    // a bare version tag counts too, for releases made before the tag template
+    for name in &[&vtag, &tag] {
+        if git::has_tag(&repo, name)? {
+            /* the tag exists in the repo */
+            println!("{} already exists as a git tag, exiting", name);
+            return Ok(());
+        }
+    }
+
+    check_not_yanked(&fname, &tag, &format)?;
+
+    let latest = git::latest_version(&repo, &template)?;
+    let from_changelog = match opts.notes_source {
+        NotesSource::Changelog => true,
+        NotesSource::Commits => false,
+        NotesSource::Auto => {
+            fname.exists()
+                && changelog::Changelog::read(&fname, &format)?
+                    .release(&tag)
+                    .is_some_and(|entry| !entry.is_empty())
+        }
+    };
+    let (desc, entry) = if from_changelog {
+        let mut desc = changelog::release_notes(&fname, &tag, opts.allow_empty_notes, &format)?;
+        if opts.cumulative {
+            let since = latest.as_ref().map(|(_, version)| version);
+            desc = changelog::cumulative_notes(&fname, &release_version, since, &scheme, &format)?;
+        }
+        let cl = changelog::Changelog::read(&fname, &format)?;
+        (desc, cl.release(&tag).cloned())
+    } else {
+        let since = latest.as_ref().map(|(tag, _)| tag.as_str());
+        let commits = git::commits_since(&repo, since)?;
+        let entry = changelog::commits::release_entry(&tag, today, &commits, format.version_level);
+        let desc = entry.notes();
+        if desc.is_empty() && !opts.allow_empty_notes {
+            return Err(anyhow!(
+                "no Conventional Commits worth releasing since {}, pass --allow-empty-notes to release anyway",
+                since.unwrap_or("the first commit")
+            ));
+        }
+        (desc, Some(entry))
+    };
+    let date = entry.as_ref().and_then(|r| r.date);
+
+    if opts.check_date && date != Some(today) {
+        return Err(anyhow!(
+            "the changelog entry for version {} is dated {}, but today is {}",
+            tag,
+            date.map(|d| d.to_string()).unwrap_or_else(|| "nothing".into()),
+            today,
+        ));
+    }
+
+    check_newer(&release_version, latest.clone(), opts.backport, &scheme)?;
+
+    // calendar versions don't say anything about what changed
+    if let (Scheme::SemVer, false, Some((_, previous)), Some((level, reason))) = (
+        scheme,
+        opts.backport,
+        &latest,
+        entry.and_then(|e| e.change_level()),
+    ) {
+        version::check_bump(previous, &release_version, level, &reason)?;
     }
 
+    let linker = Autolinker {
+        repo_url: if opts.autolink {
+            Some(format!("https://github.com/{}/{}", common.owner, common.name))
+        } else {
+            None
+        },
+        tickets: opts.ticket_link.clone(),
+    };
+    let desc = linker.link(&desc, &format, &|rev| git::is_commit(&repo, rev));
+
     let gh = Client::new(common.token)?;
+    let desc = if opts.contributors {
+        let since = latest.as_ref().map(|(tag, _)| tag.as_str());
+        let excluded = |text: &str| opts.exclude_contributor.iter().any(|re| re.is_match(text));
+        let mut names = vec![];
+        for contributor in git::contributors(&repo, since)? {
+            if excluded(&contributor.name) || excluded(&contributor.email) {
+                continue;
+            }
+            let login = if opts.contributor_logins {
+                // a failed lookup shouldn't stop the release, the name will do
+                match github_login(&gh, &common.owner, &common.name, &contributor).await {
+                    Ok(login) => login,
+                    Err(why) => {
+                        log::error!(
+                            "can't look up the GitHub login of {}: {}",
+                            contributor.name,
+                            why
+                        );
+                        None
+                    }
+                }
+            } else {
+                None
+            };
+            match login {
+                Some(login) if excluded(&login) => {}
+                Some(login) => names.push(format!("@{}", login)),
+                None => names.push(contributor.name),
+            }
+        }
+        add_contributors(desc, names, format.version_level + 1)
+    } else {
+        desc
+    };
+
+    let (draft, prerelease) = release_kind(&release_version, &opts);
 
     let release = gh.create_release(common.owner, common.name, CreateRelease{
         tag_name: vtag.clone(),
         target_commitish: "master".into(), // XXX(Christine): this may need to become an argument somehow.
-        name: format!("Version {}", tag),
+        name: release_name(opts.release_name.as_deref(), &tag, date.unwrap_or(today)),
         body: desc,
-        draft: false,
-        prerelease: false,
+        draft,
+        prerelease,
+        // prereleases should never show up as the latest release
+        make_latest: if prerelease { Some("false".into()) } else { None },
     }).await?;
 
     println!("created release for {}: {}", vtag, release.html_url);
 
     Ok(())
 }
+
+/// Fills in the release name template, see [ReleaseOptions](../struct.ReleaseOptions.html).
+fn release_name(template: Option<&str>, version: &str, date: NaiveDate) -> String {
+    template
+        .unwrap_or("Version {version}")
+        .replace("{version}", version)
+        .replace("{date}", &date.to_string())
+}
+
+/// Looks up the GitHub login of a contributor, from their noreply email
+/// (`1234+login@users.noreply.github.com`) or else the author of one of their
+/// commits. Co-authors with other emails can't be looked up.
+async fn github_login(
+    gh: &Client,
+    owner: &str,
+    repo: &str,
+    contributor: &git::Contributor,
+) -> Result<Option<String>> {
+    if let Some(user) = contributor.email.strip_suffix("@users.noreply.github.com") {
+        return Ok(user.rsplit('+').next().map(String::from));
+    }
+    match &contributor.commit {
+        Some(sha) => Ok(gh
+            .commit(owner.into(), repo.into(), sha.clone())
+            .await?
+            .author
+            .map(|author| author.login)),
+        None => Ok(None),
+    }
+}
+
+/// Appends a Contributors section listing `names` (sorted, once each) to the
+/// release notes, with a heading at `level`.
+fn add_contributors(notes: String, mut names: Vec<String>, level: u32) -> String {
+    if names.is_empty() {
+        return notes;
+    }
+    names.sort_by_key(|name| name.to_lowercase());
+    names.dedup();
+
+    let list: String = names.iter().map(|name| format!("- {}\n", name)).collect();
+    let section = format!("{} Contributors\n\n{}", "#".repeat(level as usize), list);
+    match notes.trim_end() {
+        "" => section,
+        notes => format!("{}\n\n{}", notes, section),
+    }
+}
+
+/// Refuses to release a version that is marked as yanked in the changelog,
+/// wherever the release notes come from.
+fn check_not_yanked(fname: &Path, tag: &str, format: &changelog::ParseOptions) -> Result<()> {
+    if !fname.exists() {
+        return Ok(());
+    }
+    match changelog::Changelog::read(fname, format)?.release(tag) {
+        Some(entry) if entry.yanked => Err(anyhow!(
+            "version {} is marked as yanked in the changelog, refusing to release it",
+            tag
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Makes sure `version` is newer than the latest version tagged in the repo,
+/// unless the release is a backport.
+fn check_newer(
+    version: &Version,
+    latest: Option<(String, Version)>,
+    backport: bool,
+    scheme: &Scheme,
+) -> Result<()> {
+    match latest {
+        Some((tag, latest)) if !backport && *version <= latest => Err(anyhow!(
+            "version {} is not newer than the latest release {} (tag {}), bump the version or pass --backport to release it anyway",
+            scheme.format(version),
+            scheme.format(&latest),
+            tag
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Works out if a release should be a draft and/or a prerelease. Any version
+/// with a prerelease component (`2.0.0-rc.1`) is a prerelease, and it is a
+/// draft if its first identifier (`rc`) is one of the `--draft-prerelease`
+/// identifiers.
+fn release_kind(version: &Version, opts: &ReleaseOptions) -> (bool, bool) {
+    if version.pre.is_empty() {
+        return (false, false);
+    }
+
+    let id = version.pre.as_str().split('.').next().unwrap_or("");
+    let draft = opts.draft_prerelease.iter().any(|d| d == id);
+    (draft, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::ReleaseOptions;
+    use semver::Version;
+    use std::path::Path;
+
+    #[test]
+    fn release_kind() {
+        let opts = ReleaseOptions {
+            draft_prerelease: vec!["alpha".into()],
+            ..ReleaseOptions::default()
+        };
+        let kind = |v: &str| super::release_kind(&Version::parse(v).unwrap(), &opts);
+
+        assert_eq!(kind("1.4.0"), (false, false));
+        assert_eq!(kind("2.0.0-rc.1"), (false, true));
+        assert_eq!(kind("1.4.0-beta.2"), (false, true));
+        assert_eq!(kind("2.0.0-alpha.1"), (true, true));
+        assert_eq!(kind("2.0.0-alphabet"), (false, true));
+    }
+
+    #[test]
+    fn add_contributors() {
+        let names = vec!["@yolo".to_string(), "Jane Doe".into(), "@yolo".into()];
+        assert_eq!(
+            super::add_contributors("### Fixed\n\n- Typos\n".into(), names, 3),
+            "### Fixed\n\n- Typos\n\n### Contributors\n\n- @yolo\n- Jane Doe\n"
+        );
+        assert_eq!(
+            super::add_contributors("".into(), vec!["Jane Doe".into()], 2),
+            "## Contributors\n\n- Jane Doe\n"
+        );
+        assert_eq!(super::add_contributors("- Typos\n".into(), vec![], 3), "- Typos\n");
+    }
+
+    #[test]
+    fn check_not_yanked() {
+        // the changelog is checked even when the notes come from commits
+        let check = |fname, tag| super::check_not_yanked(Path::new(fname), tag, &Default::default());
+
+        assert!(check("testdata/yanked.md", "1.3.1").is_ok());
+        assert_eq!(
+            check("testdata/yanked.md", "1.3.0").unwrap_err().to_string(),
+            "version 1.3.0 is marked as yanked in the changelog, refusing to release it"
+        );
+        assert!(check("testdata/missing.md", "1.3.0").is_ok());
+    }
+
+    #[test]
+    fn check_newer() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        let latest = || Some(("v1.10.0".to_string(), v("1.10.0")));
+        let check = |version: &str, latest, backport| {
+            super::check_newer(&v(version), latest, backport, &Default::default())
+        };
+
+        assert!(check("1.10.1", latest(), false).is_ok());
+        assert!(check("2.0.0-rc.1", latest(), false).is_ok());
+        assert!(check("0.1.0", None, false).is_ok());
+        assert!(check("1.9.2", latest(), true).is_ok());
+        assert_eq!(
+            check("1.9.2", latest(), false).unwrap_err().to_string(),
+            "version 1.9.2 is not newer than the latest release 1.10.0 (tag v1.10.0), bump the version or pass --backport to release it anyway"
+        );
+        assert!(check("1.10.0-rc.1", latest(), false).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use crate::{
+    changelog::{
+        export::{self, Format, Package},
+        Changelog, ParseOptions,
+    },
+    cmd::{ChangelogOptions, ExportOptions},
+    git,
+    version::Scheme,
+};
+use anyhow::{anyhow, Result};
+use chrono::{FixedOffset, TimeZone};
+use std::{fs, path::PathBuf};
+
+/// Exports every release in the changelog (or only the newest one) as a
+/// Debian or RPM package changelog. Releases are dated by their changelog
+/// heading, or when their tag was made if the heading has no date.
+pub async fn run(
+    fname: PathBuf,
+    opts: ExportOptions,
+    format: ChangelogOptions,
+    scheme: Scheme,
+) -> Result<()> {
+    let format: ParseOptions = (&format).into();
+    let cl = Changelog::read(&fname, &format)?;
+    let repo = git2::Repository::open(".").ok();
+
+    let name = match (&opts.package, opts.export_format) {
+        (Some(name), _) => name.clone(),
+        (None, Format::Rpm) => String::new(),
+        (None, Format::Debian) => {
+            return Err(anyhow!(
+                "the debian format needs the name of the source package, pass it with --package"
+            ))
+        }
+    };
+    let maintainer = match opts.maintainer {
+        Some(maintainer) => maintainer,
+        None => git_identity(repo.as_ref())?,
+    };
+    let template = opts.tags.template(scheme)?;
+
+    let mut entries = vec![];
+    for entry in cl.releases.iter().filter(|e| !e.is_unreleased()) {
+        let time = match entry.date {
+            Some(date) => FixedOffset::east_opt(0)
+                .unwrap()
+                .from_utc_datetime(&date.and_hms_opt(0, 0, 0).unwrap()),
+            None => {
+                let tag = template.format(&scheme.parse(&entry.version)?);
+                let time = match &repo {
+                    Some(repo) => git::tag_time(repo, &tag)?,
+                    None => None,
+                };
+                time.ok_or_else(|| {
+                    anyhow!(
+                        "version {} has no date in {} and there is no {} tag to take it from",
+                        entry.version,
+                        fname.display(),
+                        tag
+                    )
+                })?
+            }
+        };
+        entries.push((entry, time));
+        if opts.latest {
+            break;
+        }
+    }
+    if entries.is_empty() {
+        return Err(anyhow!("{} has no releases to export", fname.display()));
+    }
+
+    let package = Package {
+        name,
+        maintainer,
+        distribution: opts.distribution,
+        urgency: opts.urgency,
+        revision: opts.revision,
+    };
+    let output = export::render(opts.export_format, &package, &entries);
+    match opts.output {
+        Some(path) => {
+            fs::write(&path, output)?;
+            println!("exported {} releases to {}", entries.len(), path.display());
+        }
+        None => print!("{}", output),
+    }
+
+    Ok(())
+}
+
+/// The `Name <email>` of the user from the git config, as the maintainer.
+/// Outside of a repo only the global config is read.
+fn git_identity(repo: Option<&git2::Repository>) -> Result<String> {
+    let config = match repo {
+        Some(repo) => repo.config()?,
+        None => git2::Config::open_default()?,
+    };
+    match (config.get_string("user.name"), config.get_string("user.email")) {
+        (Ok(name), Ok(email)) => Ok(format!("{} <{}>", name, email)),
+        _ => Err(anyhow!(
+            "no maintainer to sign the changelog with, pass --maintainer \"Name <email>\" or set user.name and user.email in the git config"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+    use tempfile::tempdir;
+
+    #[test]
+    fn git_identity() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = git2::Repository::init(&dir)?;
+        let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+        config.set_str("user.name", "Jane Doe")?;
+        config.set_str("user.email", "jane@example.com")?;
+
+        assert_eq!(
+            super::git_identity(Some(&repo))?,
+            "Jane Doe <jane@example.com>"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,39 @@
+use crate::{changelog::fragments, git};
+use anyhow::{anyhow, Result};
+use std::path::PathBuf;
+
+/// Checks that every changelog fragment is valid and that at least one of
+/// them was added since `base`. `dir` is relative to the root of the repo,
+/// which is where this runs.
+pub async fn run(dir: PathBuf, base: String) -> Result<()> {
+    let repo = git2::Repository::open(".")?;
+    let dir = dir.strip_prefix(".").unwrap_or(&dir);
+    let existing = fragments::read_dir(dir)?;
+
+    let added: Vec<PathBuf> = git::changed_files(&repo, &base)?
+        .into_iter()
+        .filter(|(status, _)| {
+            matches!(
+                status,
+                git2::Delta::Added | git2::Delta::Renamed | git2::Delta::Copied
+            )
+        })
+        .map(|(_, path)| path)
+        .filter(|path| existing.iter().any(|f| f.path == *path))
+        .collect();
+
+    if added.is_empty() {
+        return Err(anyhow!(
+            "no changelog fragment was added to {} since {}, add one such as {}",
+            dir.display(),
+            base,
+            dir.join("1234.fixed.md").display()
+        ));
+    }
+
+    for path in added {
+        println!("found changelog fragment {}", path.display());
+    }
+
+    Ok(())
+}
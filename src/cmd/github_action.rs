@@ -11,7 +11,8 @@ pub async fn run(gha: GitHubAction) -> Result<()> {
     }
 
     let changelog_fname = gha.changelog_fname.clone();
+    let release = gha.release.clone();
     let common: Common = gha.into();
 
-    cmd::cut::run(common, changelog_fname).await
+    cmd::cut::run(common, changelog_fname, release).await
 }
@@ -0,0 +1,29 @@
+use crate::{changelog::lint, cmd::ChangelogOptions, version::Scheme};
+use anyhow::{anyhow, Result};
+use std::{fs, path::PathBuf};
+
+/// Lints a changelog, printing any problems as `file:line: message` and
+/// failing if there were any. With `check_bumps`, semantic versions are also
+/// checked against the changes listed under them.
+pub async fn run(
+    fname: PathBuf,
+    format: ChangelogOptions,
+    scheme: Scheme,
+    check_bumps: bool,
+) -> Result<()> {
+    let opts = (&format).into();
+    let mut diags = lint::lint(&fname, &opts, &scheme)?;
+    if check_bumps && scheme == Scheme::SemVer {
+        diags.extend(lint::bumps(&fs::read_to_string(&fname)?, &opts));
+        diags.sort_by_key(|d| d.line);
+    }
+
+    for diag in &diags {
+        println!("{}:{}", fname.display(), diag);
+    }
+
+    match diags.len() {
+        0 => Ok(()),
+        n => Err(anyhow!("found {} problem(s) in {}", n, fname.display())),
+    }
+}
@@ -1,9 +1,26 @@
+use crate::{
+    changelog::{autolink::TicketLink, HeadingPattern, ParseOptions},
+    git, version,
+};
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use regex::Regex;
+use semver::Version;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
+pub mod bump;
+pub mod check_pr;
 pub mod circleci;
 pub mod cut;
+pub mod export;
+pub mod fragments_check;
 pub mod github_action;
+pub mod lint;
+pub mod notes;
+pub mod prepare;
+pub mod version_check;
+pub mod yank;
 
 /// Common arguments across subcommands.
 #[derive(StructOpt, Debug)]
@@ -19,6 +36,299 @@ pub struct Common {
     pub name: String,
 }
 
+/// Options that change how the changelog is parsed.
+#[derive(StructOpt, Debug, Clone)]
+pub struct ChangelogOptions {
+    /// Parse the changelog as plain CommonMark instead of GitHub Flavored
+    /// Markdown
+    #[structopt(long)]
+    pub no_gfm: bool,
+    /// Heading level of the versions in the changelog, 1 for `# 1.2.0` up to
+    /// 6. Sections are one level below
+    #[structopt(
+        long,
+        env = "VERSION_HEADING_LEVEL",
+        default_value = "2",
+        parse(try_from_str = heading_level)
+    )]
+    pub version_heading_level: u32,
+    /// Regular expression for version headings that don't follow Keep a
+    /// Changelog, with `{version}` where the version goes and optionally
+    /// `{date}`, such as `v{version}` or `Version {version} \({date}\)`
+    #[structopt(long, env = "HEADING_PATTERN")]
+    pub heading_pattern: Option<HeadingPattern>,
+}
+
+impl Default for ChangelogOptions {
+    fn default() -> Self {
+        (&ParseOptions::default()).into()
+    }
+}
+
+impl From<&ParseOptions> for ChangelogOptions {
+    fn from(opts: &ParseOptions) -> Self {
+        ChangelogOptions {
+            no_gfm: !opts.gfm,
+            version_heading_level: opts.version_level,
+            heading_pattern: opts.heading_pattern.clone(),
+        }
+    }
+}
+
+fn heading_level(s: &str) -> Result<u32> {
+    match s.parse() {
+        Ok(level) if (1..=6).contains(&level) => Ok(level),
+        _ => Err(anyhow!("invalid heading level {:?}, expected 1 to 6", s)),
+    }
+}
+
+impl From<&ChangelogOptions> for ParseOptions {
+    fn from(opts: &ChangelogOptions) -> Self {
+        ParseOptions {
+            gfm: !opts.no_gfm,
+            version_level: opts.version_heading_level,
+            heading_pattern: opts.heading_pattern.clone(),
+        }
+    }
+}
+
+/// The option that says how tags are named.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct TagTemplateOptions {
+    /// Tag name, `{version}` is replaced with the version and `{name}` with
+    /// the repo name, such as `release/{version}` or `{name}-v{version}`
+    /// [default: v{version}]
+    #[structopt(long, env = "TAG_TEMPLATE")]
+    pub tag_template: Option<String>,
+}
+
+impl TagTemplateOptions {
+    /// Parses the tag template, failing if it uses `{name}` but there is no
+    /// repo name to fill in.
+    pub fn parse(&self, name: Option<&str>, scheme: version::Scheme) -> Result<git::TagTemplate> {
+        let template = self.tag_template.as_deref().unwrap_or("v{version}");
+        match (template.contains("{name}"), name) {
+            (true, None) => Err(anyhow!(
+                "the tag template {:?} uses {{name}}, pass --name or set REPO_NAME",
+                template
+            )),
+            (_, name) => git::TagTemplate::new(template, name.unwrap_or(""), scheme),
+        }
+    }
+}
+
+/// How tags are named, for subcommands that don't talk to GitHub and so only
+/// need the repo name for the tag template.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct TagOptions {
+    #[structopt(flatten)]
+    pub tag: TagTemplateOptions,
+    /// Repo name, for tag templates that use `{name}`
+    #[structopt(long, short = "R", env = "REPO_NAME")]
+    pub name: Option<String>,
+}
+
+impl TagOptions {
+    /// Parses the tag template, see
+    /// [TagTemplateOptions::parse](struct.TagTemplateOptions.html#method.parse).
+    pub fn template(&self, scheme: version::Scheme) -> Result<git::TagTemplate> {
+        self.tag.parse(self.name.as_deref(), scheme)
+    }
+}
+
+/// The option that says how versions are written.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct SchemeOptions {
+    /// How versions are written: semver, or a calendar versioning format such
+    /// as YYYY.0M.MICRO or YY.MM.MICRO
+    #[structopt(long, env = "VERSIONING", default_value = "semver")]
+    pub versioning: version::Scheme,
+}
+
+/// Options that say where the version of the project is kept.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct VersionOptions {
+    /// Where to read the version from: version (a VERSION file), cargo, npm,
+    /// pyproject, setup-cfg, maven, helm or go
+    #[structopt(long, env = "VERSION_SOURCE", default_value = "version")]
+    pub version_source: version::Kind,
+    /// Path to the file the version is read from [default: VERSION,
+    /// Cargo.toml, package.json, pyproject.toml, setup.cfg, pom.xml or
+    /// Chart.yaml depending on --version-source]
+    #[structopt(long, env = "VERSION_FILE")]
+    pub version_file: Option<PathBuf>,
+    /// Other files to write the version to when it changes, as `kind` or
+    /// `kind:path` (such as `cargo-lock` or `go:version/version.go`)
+    #[structopt(long, env = "VERSION_TARGETS", use_delimiter = true)]
+    pub version_target: Vec<version::Source>,
+    #[structopt(flatten)]
+    pub scheme: SchemeOptions,
+}
+
+impl VersionOptions {
+    /// The file the version is read from followed by every target.
+    pub fn files(&self) -> Vec<version::Source> {
+        let source = version::Source::from(self);
+        let mut result = vec![source.clone()];
+        result.extend(
+            self.version_target
+                .iter()
+                .filter(|t| **t != source)
+                .cloned(),
+        );
+        result
+    }
+
+    /// Reads the version from the source.
+    pub fn read(&self) -> Result<Version> {
+        version::Source::from(self).read(&self.scheme.versioning)
+    }
+
+    /// Writes `version` to the source and every target.
+    pub fn write(&self, version: &Version) -> Result<()> {
+        for file in self.files() {
+            file.write(version, &self.scheme.versioning)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&VersionOptions> for version::Source {
+    fn from(opts: &VersionOptions) -> Self {
+        version::Source::new(opts.version_source, opts.version_file.clone())
+    }
+}
+
+/// Options for checking pull requests, see `check-pr`.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct CheckPrOptions {
+    /// The branch or commit the pull request will be merged into
+    #[structopt(long, env = "BASE_REF", default_value = "origin/master")]
+    pub base: String,
+    /// Files that can change without a changelog entry, as patterns relative
+    /// to the root of the repo such as `docs/`, `*.md` or `.github/**`
+    #[structopt(long, env = "CHECK_PR_IGNORE", use_delimiter = true)]
+    pub ignore: Vec<check_pr::Glob>,
+    /// Directory of changelog fragments, adding one counts as updating the
+    /// changelog
+    #[structopt(long, env = "CHANGELOG_FRAGMENTS")]
+    pub fragments: Option<PathBuf>,
+    /// Labels of the pull request
+    #[structopt(long, env = "PR_LABELS", use_delimiter = true)]
+    pub label: Vec<String>,
+    /// Skip the checks if the pull request has this label
+    #[structopt(long, env = "SKIP_CHANGELOG_LABEL", default_value = "skip-changelog")]
+    pub skip_label: String,
+    /// Skip the checks if a commit in the pull request has this trailer, such
+    /// as `Skip-Changelog: true`
+    #[structopt(long, env = "SKIP_CHANGELOG_TRAILER", default_value = "Skip-Changelog")]
+    pub skip_trailer: String,
+}
+
+/// Options for exporting the changelog, see `export`.
+#[derive(StructOpt, Debug, Clone)]
+pub struct ExportOptions {
+    /// What to export to: debian (for `debian/changelog`) or rpm (for the
+    /// `%changelog` section of a spec file)
+    #[structopt(long = "format", default_value = "debian")]
+    pub export_format: crate::changelog::export::Format,
+    /// Only export the newest release
+    #[structopt(long)]
+    pub latest: bool,
+    /// Name of the source package, needed for debian
+    #[structopt(long, env = "PACKAGE_NAME")]
+    pub package: Option<String>,
+    /// Who packaged the releases, as `Name <email>` [default: user.name and
+    /// user.email from the git config]
+    #[structopt(long, env = "MAINTAINER")]
+    pub maintainer: Option<String>,
+    /// The distribution the releases are uploaded to, for debian
+    #[structopt(long, env = "DISTRIBUTION", default_value = "unstable")]
+    pub distribution: String,
+    /// How important it is to upgrade (low, medium, high, emergency or
+    /// critical), for debian
+    #[structopt(long, env = "URGENCY", default_value = "medium")]
+    pub urgency: String,
+    /// The package revision added to every version, such as the `1` of
+    /// `1.2.0-1`
+    #[structopt(long, default_value = "1")]
+    pub revision: String,
+    /// How tags are named, to date releases that have no date in the
+    /// changelog by when their tag was made
+    #[structopt(flatten)]
+    pub tags: TagOptions,
+    /// Write to this file instead of standard output
+    #[structopt(long, short)]
+    pub output: Option<PathBuf>,
+}
+
+/// Options that change how a release is cut, shared by every subcommand that
+/// can cut one.
+#[derive(StructOpt, Debug, Clone, Default)]
+pub struct ReleaseOptions {
+    /// Create the release even if the changelog has no notes for this version
+    #[structopt(long)]
+    pub allow_empty_notes: bool,
+    /// Release name, `{version}` and `{date}` are replaced with the version and
+    /// the date in its changelog heading (or today's date if it has none)
+    /// [default: Version {version}]
+    #[structopt(long, env = "RELEASE_NAME")]
+    pub release_name: Option<String>,
+    #[structopt(flatten)]
+    pub tag: TagTemplateOptions,
+    /// Fail unless the changelog heading for this version is dated today (UTC)
+    #[structopt(long)]
+    pub check_date: bool,
+    /// Prerelease identifiers (such as `alpha` for `2.0.0-alpha.1`) that are
+    /// created as draft releases instead of being published
+    #[structopt(long, env = "DRAFT_PRERELEASES", use_delimiter = true)]
+    pub draft_prerelease: Vec<String>,
+    /// Allow releasing a version that is not newer than the latest version
+    /// tagged in the repo, such as a fix backported to a maintenance branch.
+    /// This also skips checking that the version was bumped enough for the
+    /// changes in its changelog entry
+    #[structopt(long)]
+    pub backport: bool,
+    /// Where the release notes come from: changelog, commits (Conventional
+    /// Commits since the latest release) or auto (the changelog if it has an
+    /// entry for this version, commits if it doesn't)
+    #[structopt(long, env = "NOTES_SOURCE", default_value = "changelog")]
+    pub notes_source: cut::NotesSource,
+    /// Include the notes of every version in the changelog since the latest
+    /// release, such as versions that were skipped or never released
+    #[structopt(long)]
+    pub cumulative: bool,
+    /// Link `#123` and `GH-123` to issues and pull requests and commit hashes
+    /// to commits in the release notes
+    #[structopt(long)]
+    pub autolink: bool,
+    /// Link ticket keys in the release notes to an issue tracker, as
+    /// `PREFIX=URL` where `{key}` in the URL is replaced with the key, such as
+    /// `WAT=https://example.atlassian.net/browse/{key}` for `WAT-2392`
+    #[structopt(long, env = "TICKET_LINKS", use_delimiter = true)]
+    pub ticket_link: Vec<TicketLink>,
+    /// Thank everyone who authored or co-authored (with a `Co-authored-by`
+    /// trailer) a commit since the latest release in a Contributors section
+    /// at the end of the release notes. Names and emails are merged with the
+    /// repo's `.mailmap`
+    #[structopt(long)]
+    pub contributors: bool,
+    /// Mention contributors by their GitHub login instead of their name,
+    /// looked up from their commits with the GitHub API
+    #[structopt(long)]
+    pub contributor_logins: bool,
+    /// Leave contributors whose name, email or GitHub login matches one of
+    /// these regular expressions out of the Contributors section, such as
+    /// bots. Pass it once for each expression, `EXCLUDE_CONTRIBUTORS` holds
+    /// just one (combine several with `|`)
+    #[structopt(long, env = "EXCLUDE_CONTRIBUTORS", default_value = r"\[bot\]")]
+    pub exclude_contributor: Vec<Regex>,
+    #[structopt(flatten)]
+    pub format: ChangelogOptions,
+    #[structopt(flatten)]
+    pub version: VersionOptions,
+}
+
 #[derive(StructOpt, Debug)]
 pub struct GitHubAction {
     // these are set by GitHub Actions:
@@ -37,6 +347,8 @@ pub struct GitHubAction {
     /// GitHub token to authenticate with
     #[structopt(long, env = "GITHUB_TOKEN")]
     pub token: String,
+    #[structopt(flatten)]
+    pub release: ReleaseOptions,
 }
 
 // Conversion function for turning a GitHubAction into a Common
@@ -74,6 +386,8 @@ pub struct CircleCIEnv {
     /// GitHub token to authenticate with
     #[structopt(long, env = "GITHUB_TOKEN", hide_env_values = true)]
     pub token: String,
+    #[structopt(flatten)]
+    pub release: ReleaseOptions,
 }
 
 // Conversion function for turning a CircleCIEnv into a Common.
@@ -94,6 +408,44 @@ impl From<CircleCIEnv> for Common {
 #[derive(StructOpt, Debug)]
 #[structopt(about = "A simple release management tool")]
 pub enum Cmd {
+    /// Bumps the version of the project
+    Bump {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        /// Also add a heading for the new version to the changelog
+        #[structopt(long)]
+        update_changelog: bool,
+        /// Prerelease identifier (such as `rc`) for premajor, preminor,
+        /// prepatch and prerelease bumps
+        #[structopt(long)]
+        id: Option<String>,
+        #[structopt(flatten)]
+        tags: TagOptions,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        version: VersionOptions,
+        /// major, minor, patch, premajor, preminor, prepatch or prerelease, or
+        /// auto to pick major, minor or patch from the Unreleased section of
+        /// the changelog
+        level: bump::Target,
+    },
+
+    /// Checks that a pull request updates the changelog along with the code,
+    /// failing if it changes the version without a changelog entry for it
+    CheckPr {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        #[structopt(flatten)]
+        options: CheckPrOptions,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        version: VersionOptions,
+    },
+
     /// Runs releases as triggered by CircleCI
     Circle {
         #[structopt(flatten)]
@@ -107,6 +459,27 @@ pub enum Cmd {
         /// Changelog location
         #[structopt(long, short, default_value = "./CHANGELOG.md")]
         changelog: PathBuf,
+        #[structopt(flatten)]
+        release: ReleaseOptions,
+    },
+
+    /// Exports the changelog as a Debian or RPM package changelog
+    Export {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        #[structopt(flatten)]
+        options: ExportOptions,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        scheme: SchemeOptions,
+    },
+
+    /// Works with changelog fragments
+    Fragments {
+        #[structopt(subcommand)]
+        cmd: FragmentsCmd,
     },
 
     /// Runs releases as triggered by GitHub Actions
@@ -114,4 +487,106 @@ pub enum Cmd {
         #[structopt(flatten)]
         gha: GitHubAction,
     },
+
+    /// Checks that the changelog follows the Keep a Changelog format
+    Lint {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        scheme: SchemeOptions,
+        /// Also check that every version was bumped enough for the changes
+        /// listed under it, such as a major release for removed features
+        #[structopt(long)]
+        check_bumps: bool,
+    },
+
+    /// Generates release notes from the Conventional Commits since the latest
+    /// release
+    Notes {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        /// Add the notes to the changelog under a heading for the current
+        /// version instead of printing them
+        #[structopt(long)]
+        write: bool,
+        #[structopt(flatten)]
+        tags: TagOptions,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        version: VersionOptions,
+    },
+
+    /// Promotes the Unreleased changelog section into a new version
+    Prepare {
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        /// Release date for the new version heading [default: today (UTC)]
+        #[structopt(long)]
+        date: Option<NaiveDate>,
+        /// Directory of changelog fragments (such as `changes/1234.added.md`)
+        /// to assemble into the new version instead of the Unreleased section
+        #[structopt(long, env = "CHANGELOG_FRAGMENTS")]
+        fragments: Option<PathBuf>,
+        #[structopt(flatten)]
+        tags: TagOptions,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        options: VersionOptions,
+        /// The new version, or a bump level (such as major, minor or patch)
+        /// for the current version
+        version: String,
+    },
+
+    /// Manages the version of the project
+    Version {
+        #[structopt(subcommand)]
+        cmd: VersionCmd,
+    },
+
+    /// Marks the GitHub releases of versions yanked in the changelog
+    Yank {
+        #[structopt(flatten)]
+        common: Common,
+        /// Changelog location
+        #[structopt(long, short, default_value = "./CHANGELOG.md")]
+        changelog: PathBuf,
+        #[structopt(flatten)]
+        format: ChangelogOptions,
+        #[structopt(flatten)]
+        tag: TagTemplateOptions,
+        #[structopt(flatten)]
+        scheme: SchemeOptions,
+    },
+}
+
+/// The subcommands of `palisade version`.
+#[derive(StructOpt, Debug)]
+pub enum VersionCmd {
+    /// Checks that every version target has the same version as the source
+    Check {
+        #[structopt(flatten)]
+        version: VersionOptions,
+    },
+}
+
+/// The subcommands of `palisade fragments`.
+#[derive(StructOpt, Debug)]
+pub enum FragmentsCmd {
+    /// Checks that a changelog fragment was added since the base branch, for
+    /// pull requests
+    Check {
+        /// Directory of changelog fragments
+        #[structopt(long, env = "CHANGELOG_FRAGMENTS", default_value = "changes")]
+        fragments: PathBuf,
+        /// The branch or commit the pull request will be merged into
+        #[structopt(long, env = "BASE_REF", default_value = "origin/master")]
+        base: String,
+    },
 }
@@ -0,0 +1,61 @@
+use crate::{
+    changelog,
+    cmd::{ChangelogOptions, TagOptions, VersionOptions},
+    git,
+};
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use std::{fs, path::PathBuf};
+
+/// Generates release notes for the current version from the Conventional
+/// Commits since the latest release and prints them, or with `write` adds them
+/// to the changelog under a heading for the version dated today.
+pub async fn run(
+    fname: PathBuf,
+    write: bool,
+    tags: TagOptions,
+    format: ChangelogOptions,
+    options: VersionOptions,
+) -> Result<()> {
+    let repo = git2::Repository::open(".")?;
+    let scheme = options.scheme.versioning;
+    let template = tags.template(scheme)?;
+
+    let version = scheme.format(&options.read()?);
+    let today = Utc::now().naive_utc().date();
+    let latest = git::latest_version(&repo, &template)?;
+    let since = latest.as_ref().map(|(tag, _)| tag.as_str());
+    let commits = git::commits_since(&repo, since)?;
+    let entry =
+        changelog::commits::release_entry(&version, today, &commits, format.version_heading_level);
+    let notes = entry.notes();
+
+    if notes.is_empty() {
+        return Err(anyhow!(
+            "no Conventional Commits worth releasing since {}",
+            since.unwrap_or("the first commit")
+        ));
+    }
+    if !write {
+        print!("{}", notes);
+        return Ok(());
+    }
+
+    let data = match fs::read_to_string(&fname) {
+        Ok(data) => data,
+        Err(why) if why.kind() == std::io::ErrorKind::NotFound => "# Changelog\n".into(),
+        Err(why) => return Err(why.into()),
+    };
+    let data = changelog::edit::insert_release(
+        &data,
+        &version,
+        today,
+        &notes,
+        &(&format).into(),
+        &template,
+    )?;
+    fs::write(&fname, data)?;
+    println!("added notes for version {} to {}", version, fname.display());
+
+    Ok(())
+}
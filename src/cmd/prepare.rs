@@ -0,0 +1,70 @@
+use crate::{
+    changelog,
+    cmd::{ChangelogOptions, TagOptions, VersionOptions},
+    version,
+};
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, Utc};
+use std::{fs, path::PathBuf};
+
+/// Promotes the Unreleased section of the changelog into a new version and
+/// writes that version to the version source and targets. `target` is either
+/// a bump level (such as major, minor or patch) or an explicit version. If
+/// there are changelog fragments in `fragments`, they are assembled into the
+/// new version instead and deleted. Compare links in the changelog are moved
+/// to tags named by `tags`.
+pub async fn run(
+    fname: PathBuf,
+    target: String,
+    date: Option<NaiveDate>,
+    fragments: Option<PathBuf>,
+    tags: TagOptions,
+    format: ChangelogOptions,
+    options: VersionOptions,
+) -> Result<()> {
+    let scheme = options.scheme.versioning;
+    let tags = tags.template(scheme)?;
+    let date = date.unwrap_or_else(|| Utc::now().naive_utc().date());
+    let next = match target.parse::<version::Level>() {
+        Ok(level) => scheme.bump(&options.read()?, level, None, date)?,
+        Err(_) => version::parse_version(&target, "version argument", &scheme)?,
+    };
+
+    let format = (&format).into();
+    let fragments = match &fragments {
+        Some(dir) => changelog::fragments::read_dir(dir)?,
+        None => vec![],
+    };
+
+    let data = fs::read_to_string(&fname)?;
+    let data = if fragments.is_empty() {
+        changelog::edit::promote_unreleased(&data, &scheme.format(&next), date, &format, &tags)?
+    } else {
+        let cl = changelog::Changelog::parse(&data, &format)?;
+        if cl.unreleased().is_some_and(|e| !e.is_empty()) {
+            return Err(anyhow!(
+                "both the Unreleased section of {} and the changelog fragments list changes, move the Unreleased changes into fragments",
+                fname.display()
+            ));
+        }
+        let version = scheme.format(&next);
+        let entry =
+            changelog::fragments::release_entry(&version, date, &fragments, format.version_level);
+        changelog::edit::insert_release(&data, &version, date, &entry.notes(), &format, &tags)?
+    };
+    fs::write(&fname, data)?;
+    options.write(&next)?;
+    for fragment in &fragments {
+        fs::remove_file(&fragment.path)?;
+    }
+
+    let files: Vec<String> = options.files().iter().map(|f| f.to_string()).collect();
+    println!(
+        "prepared version {} in {} and {}",
+        scheme.format(&next),
+        fname.display(),
+        files.join(", ")
+    );
+
+    Ok(())
+}
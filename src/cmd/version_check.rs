@@ -0,0 +1,47 @@
+use crate::{cmd::VersionOptions, version};
+use anyhow::{anyhow, Result};
+
+/// Checks that every version target agrees with the version source, printing
+/// any that don't and failing if there were any.
+pub async fn run(opts: VersionOptions) -> Result<()> {
+    let scheme = opts.scheme.versioning;
+    let source = version::Source::from(&opts);
+    let expected = source.read(&scheme)?;
+    let mut problems = 0;
+
+    for target in opts.files().iter().skip(1) {
+        match target.read(&scheme) {
+            Ok(found) if found == expected => {}
+            Ok(found) => {
+                problems += 1;
+                println!(
+                    "{}: version is {}, but {} has {}",
+                    target,
+                    scheme.format(&found),
+                    source,
+                    scheme.format(&expected)
+                );
+            }
+            Err(why) => {
+                problems += 1;
+                println!("{}: {}", target, why);
+            }
+        }
+    }
+
+    match problems {
+        0 => {
+            println!(
+                "all version targets match version {} in {}",
+                scheme.format(&expected),
+                source
+            );
+            Ok(())
+        }
+        n => Err(anyhow!(
+            "found {} version target(s) that don't match {}",
+            n,
+            source
+        )),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    changelog,
+    cmd::{ChangelogOptions, Common, TagTemplateOptions},
+    version::Scheme,
+};
+use anyhow::Result;
+use github::{Client, UpdateRelease};
+use std::path::PathBuf;
+
+/// Marks the GitHub release of every version that is yanked in the changelog
+/// (`## [1.3.0] - 2024-02-01 [YANKED]`): a warning goes at the top of its
+/// notes and it is turned into a prerelease so it stops being the latest
+/// release. Releases that were already marked are left alone.
+pub async fn run(
+    common: Common,
+    fname: PathBuf,
+    format: ChangelogOptions,
+    tag: TagTemplateOptions,
+    scheme: Scheme,
+) -> Result<()> {
+    let cl = changelog::Changelog::read(&fname, &(&format).into())?;
+    let template = tag.parse(Some(&common.name), scheme)?;
+    let gh = Client::new(common.token)?;
+
+    for entry in cl.releases.iter().filter(|r| r.yanked) {
+        let version = scheme.parse(&entry.version)?;
+        let tag = template.format(&version);
+        let release = match gh
+            .release_by_tag(common.owner.clone(), common.name.clone(), tag.clone())
+            .await?
+        {
+            Some(release) => release,
+            None => {
+                println!("{} has no GitHub release, skipping", tag);
+                continue;
+            }
+        };
+
+        let body = yanked_body(&release.body, &scheme.format(&version));
+        if body.is_none() && release.prerelease {
+            println!("{} is already marked as yanked", tag);
+            continue;
+        }
+
+        let release = gh
+            .update_release(
+                common.owner.clone(),
+                common.name.clone(),
+                release.id,
+                UpdateRelease {
+                    body,
+                    prerelease: Some(true),
+                    make_latest: Some("false".into()),
+                    ..UpdateRelease::default()
+                },
+            )
+            .await?;
+        println!("marked {} as yanked: {}", tag, release.html_url);
+    }
+
+    Ok(())
+}
+
+/// Adds the yanked warning to the top of release notes, or returns `None` if
+/// it is already there.
+fn yanked_body(body: &str, version: &str) -> Option<String> {
+    let banner = format!(
+        "> [!WARNING]\n> Version {} has been yanked, don't use it. See the changelog for details.",
+        version
+    );
+    if body.starts_with(&banner) {
+        return None;
+    }
+
+    Some(format!("{}\n\n{}", banner, body))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn yanked_body() {
+        let body = super::yanked_body("### Added\n\n- Saving twice in a row\n", "1.3.0").unwrap();
+        assert_eq!(
+            body,
+            "> [!WARNING]\n> Version 1.3.0 has been yanked, don't use it. See the changelog for details.\n\n### Added\n\n- Saving twice in a row\n"
+        );
+        assert_eq!(super::yanked_body(&body, "1.3.0"), None);
+    }
+}
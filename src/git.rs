@@ -1,5 +1,9 @@
-use anyhow::Result;
+use crate::version::Scheme;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, TimeZone};
 use git2::Repository;
+use semver::Version;
+use std::path::{Path, PathBuf};
 
 /// Returns Ok(true) if the given repository has the given tag.
 pub(crate) fn has_tag(repo: &Repository, tag: &String) -> Result<bool> {
@@ -20,6 +24,271 @@ pub(crate) fn has_tag(repo: &Repository, tag: &String) -> Result<bool> {
     Ok(false)
 }
 
+/// How tags are named, such as `v{version}`, `release/{version}` or
+/// `{name}-v{version}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagTemplate {
+    prefix: String,
+    suffix: String,
+    scheme: Scheme,
+}
+
+impl TagTemplate {
+    /// Parses a template, filling in `{name}` with `name`. The template must
+    /// contain `{version}` exactly once, which is written in `scheme`.
+    pub fn new(template: &str, name: &str, scheme: Scheme) -> Result<Self> {
+        let template = template.replace("{name}", name);
+        let mut parts = template.split("{version}");
+
+        match (parts.next(), parts.next(), parts.next()) {
+            (Some(prefix), Some(suffix), None) => Ok(TagTemplate {
+                prefix: prefix.into(),
+                suffix: suffix.into(),
+                scheme,
+            }),
+            _ => Err(anyhow!(
+                "the tag template {:?} must contain {{version}} exactly once",
+                template
+            )),
+        }
+    }
+
+    /// Returns the name of the tag for `version`.
+    pub fn format(&self, version: &Version) -> String {
+        format!(
+            "{}{}{}",
+            self.prefix,
+            self.scheme.format(version),
+            self.suffix
+        )
+    }
+
+    /// Returns the name of the tag for a version written out, such as one
+    /// from a changelog heading.
+    pub fn tag(&self, version: &str) -> Result<String> {
+        Ok(self.format(&self.scheme.parse(version)?))
+    }
+
+    /// Returns the version a tag is for, or `None` if the tag doesn't follow
+    /// the template.
+    pub fn parse(&self, tag: &str) -> Option<Version> {
+        let version = tag.strip_prefix(&self.prefix)?.strip_suffix(&self.suffix)?;
+        self.scheme.parse(version).ok()
+    }
+}
+
+impl Default for TagTemplate {
+    fn default() -> Self {
+        TagTemplate {
+            prefix: "v".into(),
+            suffix: "".into(),
+            scheme: Scheme::default(),
+        }
+    }
+}
+
+/// Returns every tag in the repository that follows the template along with
+/// its version, ordered from oldest to newest by semver precedence. Other tags
+/// are ignored.
+pub(crate) fn versions(
+    repo: &Repository,
+    template: &TagTemplate,
+) -> Result<Vec<(String, Version)>> {
+    let mut result: Vec<(String, Version)> = repo
+        .tag_names(None)?
+        .iter()
+        .flatten()
+        .filter_map(|tag| {
+            let version = template.parse(tag)?;
+            Some((tag.to_string(), version))
+        })
+        .collect();
+
+    result.sort_by(|a, b| a.1.cmp(&b.1));
+    Ok(result)
+}
+
+/// Returns the newest tag in the repository that follows the template.
+pub(crate) fn latest_version(
+    repo: &Repository,
+    template: &TagTemplate,
+) -> Result<Option<(String, Version)>> {
+    Ok(versions(repo, template)?.pop())
+}
+
+/// Returns the short id and message of every commit reachable from `HEAD` but
+/// not from the tag `since`, newest first. Without `since` this is the whole
+/// history.
+pub(crate) fn commits_since(
+    repo: &Repository,
+    since: Option<&str>,
+) -> Result<Vec<(String, String)>> {
+    walk_since(repo, since)?
+        .map(|oid| {
+            let commit = repo.find_commit(oid?)?;
+            let id = commit.as_object().short_id()?;
+            Ok((
+                id.as_str().unwrap_or_default().to_string(),
+                commit.message().unwrap_or_default().to_string(),
+            ))
+        })
+        .collect()
+}
+
+/// Walks the commits reachable from `HEAD` but not from the tag `since`,
+/// newest first.
+fn walk_since<'r>(repo: &'r Repository, since: Option<&str>) -> Result<git2::Revwalk<'r>> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::TIME)?;
+    walk.push_head()?;
+    if let Some(tag) = since {
+        let commit = repo.revparse_single(tag)?.peel_to_commit()?;
+        walk.hide(commit.id())?;
+    }
+    Ok(walk)
+}
+
+/// Someone who authored or co-authored a commit, as named by the repo's
+/// `.mailmap`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Contributor {
+    pub name: String,
+    pub email: String,
+    /// The id of a commit they authored, to look up their GitHub account
+    /// with. Co-authors who didn't author any commits don't have one.
+    pub commit: Option<String>,
+}
+
+/// Returns everyone who authored a commit reachable from `HEAD` but not from
+/// the tag `since`, or co-authored one with a `Co-authored-by` trailer,
+/// ordered by name. People with more than one name or email are merged with
+/// the `.mailmap` file, otherwise each email is a different person.
+pub(crate) fn contributors(repo: &Repository, since: Option<&str>) -> Result<Vec<Contributor>> {
+    let mailmap = repo.mailmap()?;
+    let mut result: Vec<Contributor> = vec![];
+    let mut add = |sig: git2::Signature, commit: Option<String>| {
+        let (name, email) = match (sig.name(), sig.email()) {
+            (Some(name), Some(email)) => (name.to_string(), email.to_string()),
+            _ => return,
+        };
+        let contributor = Contributor {
+            name,
+            email,
+            commit,
+        };
+        // prefer how people wrote their name when authoring a commit over how
+        // others wrote it in trailers
+        match result
+            .iter_mut()
+            .find(|c| c.email.eq_ignore_ascii_case(&contributor.email))
+        {
+            Some(known) if known.commit.is_none() && contributor.commit.is_some() => {
+                *known = contributor
+            }
+            Some(_) => {}
+            None => result.push(contributor),
+        }
+    };
+
+    for oid in walk_since(repo, since)? {
+        let commit = repo.find_commit(oid?)?;
+        add(
+            commit.author_with_mailmap(&mailmap)?,
+            Some(commit.id().to_string()),
+        );
+
+        let message = commit.message().unwrap_or_default();
+        for (name, email) in message.lines().filter_map(co_author) {
+            if let Ok(sig) = git2::Signature::now(name, email) {
+                add(mailmap.resolve_signature(&sig)?, None);
+            }
+        }
+    }
+
+    result.sort_by_key(|c| c.name.to_lowercase());
+    Ok(result)
+}
+
+/// Parses a `Co-authored-by: Name <email>` trailer into the name and email.
+fn co_author(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let (key, value) = line.split_at(line.find(':')?);
+    if !key.eq_ignore_ascii_case("co-authored-by") {
+        return None;
+    }
+    let value = value[1..].trim().strip_suffix('>')?;
+    let open = value.rfind('<')?;
+    Some((value[..open].trim(), value[open + 1..].trim()))
+}
+
+/// Checks if `rev` names a commit in the repo, such as a short commit hash.
+pub(crate) fn is_commit(repo: &Repository, rev: &str) -> bool {
+    repo.revparse_single(rev)
+        .and_then(|obj| obj.peel_to_commit())
+        .is_ok()
+}
+
+/// Returns the files that changed between the merge base of `base` and
+/// `HEAD`, and `HEAD`, along with how they changed. This is what a pull request
+/// from `HEAD` into `base` would change. Paths are relative to the root of the
+/// repository.
+pub(crate) fn changed_files(repo: &Repository, base: &str) -> Result<Vec<(git2::Delta, PathBuf)>> {
+    let head = repo.head()?.peel_to_commit()?;
+    let merge_base = merge_base(repo, base)?;
+    let diff = repo.diff_tree_to_tree(Some(&merge_base.tree()?), Some(&head.tree()?), None)?;
+
+    Ok(diff
+        .deltas()
+        .filter_map(|d| {
+            let file = d.new_file().path().or_else(|| d.old_file().path())?;
+            Some((d.status(), file.to_path_buf()))
+        })
+        .collect())
+}
+
+/// Returns when the tag `tag` was made: when it was tagged for annotated tags,
+/// otherwise when the commit it points to was made. `None` if there is no such
+/// tag.
+pub(crate) fn tag_time(repo: &Repository, tag: &str) -> Result<Option<DateTime<FixedOffset>>> {
+    let obj = match repo.revparse_single(&format!("refs/tags/{}", tag)) {
+        Ok(obj) => obj,
+        Err(why) if why.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(why) => return Err(why.into()),
+    };
+    let time = match obj.as_tag().and_then(|t| t.tagger()) {
+        Some(tagger) => tagger.when(),
+        None => obj.peel_to_commit()?.time(),
+    };
+
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60)
+        .ok_or_else(|| anyhow!("tag {} has an invalid time zone", tag))?;
+    Ok(offset.timestamp_opt(time.seconds(), 0).single())
+}
+
+/// Returns the merge base of `base` and `HEAD`, the commit a pull request from
+/// `HEAD` into `base` starts from.
+pub(crate) fn merge_base<'r>(repo: &'r Repository, base: &str) -> Result<git2::Commit<'r>> {
+    let head = repo.head()?.peel_to_commit()?;
+    let base = repo.revparse_single(base)?.peel_to_commit()?;
+    Ok(repo.find_commit(repo.merge_base(base.id(), head.id())?)?)
+}
+
+/// Returns the contents of the file at `path` (relative to the root of the
+/// repository) as of `commit`, or `None` if it didn't exist then.
+pub(crate) fn read_file(
+    repo: &Repository,
+    commit: &git2::Commit,
+    path: &Path,
+) -> Result<Option<String>> {
+    let entry = match commit.tree()?.get_path(path) {
+        Ok(entry) => entry,
+        Err(why) if why.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(why) => return Err(why.into()),
+    };
+    let blob = entry.to_object(repo)?.peel_to_blob()?;
+    Ok(Some(String::from_utf8(blob.content().to_vec())?))
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
@@ -58,4 +327,230 @@ mod tests {
 
         Ok(())
     }
+
+    /// Checks that tags are ordered by semver precedence, not alphabetically.
+    #[test]
+    fn versions() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(&dir)?;
+        let sig = &Signature::now("Palisade", "p@lisa.de")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        repo.commit(
+            Some("HEAD"),
+            sig,
+            sig,
+            "test commit please ignore",
+            &tree,
+            &[],
+        )?;
+
+        let obj = repo.revparse_single("HEAD")?;
+        for tag in &["v1.9.1", "nightly", "v1.10.0", "0.1.0", "v1.10.0-rc.1"] {
+            repo.tag_lightweight(tag, &obj, false)?;
+        }
+
+        let template = super::TagTemplate::default();
+        let tags: Vec<String> = super::versions(&repo, &template)?
+            .into_iter()
+            .map(|(t, _)| t)
+            .collect();
+        assert_eq!(tags, vec!["v1.9.1", "v1.10.0-rc.1", "v1.10.0"]);
+
+        let (tag, version) = super::latest_version(&repo, &template)?.unwrap();
+        assert_eq!(tag, "v1.10.0");
+        assert_eq!(version, semver::Version::new(1, 10, 0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tag_template() -> Result<()> {
+        let version = semver::Version::parse("1.2.0-rc.1")?;
+        let cases = vec![
+            ("v{version}", "v1.2.0-rc.1"),
+            ("{version}", "1.2.0-rc.1"),
+            ("release/{version}", "release/1.2.0-rc.1"),
+            ("{name}-v{version}", "yolo-v1.2.0-rc.1"),
+        ];
+
+        for (template, tag) in cases {
+            let template = super::TagTemplate::new(template, "yolo", Default::default())?;
+            assert_eq!(template.format(&version), tag);
+            assert_eq!(template.parse(tag), Some(version.clone()));
+        }
+
+        let template = super::TagTemplate::new("{name}-v{version}", "yolo", Default::default())?;
+        assert_eq!(template.parse("swag-v1.2.0"), None);
+        assert_eq!(template.parse("yolo-vnext"), None);
+        assert!(super::TagTemplate::new("latest", "yolo", Default::default()).is_err());
+
+        let template = super::TagTemplate::new("v{version}", "yolo", "YYYY.0M.MICRO".parse()?)?;
+        assert_eq!(
+            template.parse("v2024.05.1"),
+            Some(semver::Version::new(2024, 5, 1))
+        );
+        assert_eq!(
+            template.format(&semver::Version::new(2024, 5, 1)),
+            "v2024.05.1"
+        );
+        assert_eq!(template.parse("v1.2.0"), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn tag_time() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(&dir)?;
+        let commit_sig = &Signature::new("Palisade", "p@lisa.de", &Time::new(1590969600, 0))?;
+        let tag_sig = &Signature::new("Palisade", "p@lisa.de", &Time::new(1591021800, 120))?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+        let oid = repo.commit(Some("HEAD"), commit_sig, commit_sig, "first", &tree, &[])?;
+        let obj = repo.find_object(oid, None)?;
+        repo.tag_lightweight("v1.0.0", &obj, false)?;
+        repo.tag("v1.1.0", &obj, tag_sig, "version 1.1.0", false)?;
+
+        let time = |tag| -> Result<Option<String>> {
+            Ok(super::tag_time(&repo, tag)?.map(|t| t.to_rfc3339()))
+        };
+        assert_eq!(
+            time("v1.0.0")?.as_deref(),
+            Some("2020-06-01T00:00:00+00:00")
+        );
+        assert_eq!(
+            time("v1.1.0")?.as_deref(),
+            Some("2020-06-01T16:30:00+02:00")
+        );
+        assert_eq!(time("v2.0.0")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn commits_since() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(&dir)?;
+        let sig = &Signature::now("Palisade", "p@lisa.de")?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+
+        let mut parents = vec![];
+        for message in &["feat: first", "fix: second", "feat: third"] {
+            let parent_refs: Vec<&Commit> = parents.iter().collect();
+            let oid = repo.commit(Some("HEAD"), sig, sig, message, &tree, &parent_refs)?;
+            parents = vec![repo.find_commit(oid)?];
+            if *message == "feat: first" {
+                repo.tag_lightweight("v0.1.0", parents[0].as_object(), false)?;
+            }
+        }
+
+        let messages = |since| -> Result<Vec<String>> {
+            Ok(super::commits_since(&repo, since)?
+                .into_iter()
+                .map(|(_, m)| m)
+                .collect())
+        };
+        assert_eq!(
+            messages(Some("v0.1.0"))?,
+            vec!["feat: third", "fix: second"]
+        );
+        assert_eq!(messages(None)?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn contributors() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(&dir)?;
+        std::fs::write(
+            dir.path().join(".mailmap"),
+            "Jane Doe <jane@example.com> <jdoe@old.example.com>\n",
+        )?;
+        let tree = repo.find_tree(repo.index()?.write_tree()?)?;
+
+        let commits = vec![
+            ("Palisade", "p@lisa.de", "feat: first"),
+            ("jdoe", "jdoe@old.example.com", "fix: second"),
+            (
+                "Palisade",
+                "p@lisa.de",
+                "feat: third\n\nCo-authored-by: Bob <bob@example.com>\nco-authored-by: Jane Doe <JANE@example.com>",
+            ),
+        ];
+        let mut parents = vec![];
+        for (name, email, message) in commits {
+            let sig = &Signature::now(name, email)?;
+            let parent_refs: Vec<&Commit> = parents.iter().collect();
+            let oid = repo.commit(Some("HEAD"), sig, sig, message, &tree, &parent_refs)?;
+            parents = vec![repo.find_commit(oid)?];
+            if message == "feat: first" {
+                repo.tag_lightweight("v0.1.0", parents[0].as_object(), false)?;
+            }
+        }
+
+        let contributors = super::contributors(&repo, Some("v0.1.0"))?;
+        let names: Vec<(&str, &str, bool)> = contributors
+            .iter()
+            .map(|c| (c.name.as_str(), c.email.as_str(), c.commit.is_some()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("Bob", "bob@example.com", false),
+                ("Jane Doe", "jane@example.com", true),
+                ("Palisade", "p@lisa.de", true),
+            ]
+        );
+        assert_eq!(super::contributors(&repo, None)?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn changed_files() -> Result<()> {
+        let dir = tempdir()?;
+        let repo = Repository::init(&dir)?;
+        let sig = &Signature::now("Palisade", "p@lisa.de")?;
+        let commit = |files: &[&str], parents: &[&Commit]| -> Result<Oid> {
+            let mut index = repo.index()?;
+            for file in files {
+                std::fs::create_dir_all(dir.path().join(file).parent().unwrap())?;
+                File::create(dir.path().join(file))?;
+                index.add_path(Path::new(file))?;
+            }
+            index.write()?;
+            let tree = repo.find_tree(index.write_tree()?)?;
+            Ok(repo.commit(
+                Some("HEAD"),
+                sig,
+                sig,
+                "test commit please ignore",
+                &tree,
+                parents,
+            )?)
+        };
+
+        let base = repo.find_commit(commit(&["VERSION"], &[])?)?;
+        repo.branch("base", &base, false)?;
+        let next = repo.find_commit(commit(&["changes/12.added.md"], &[&base])?)?;
+        commit(&["src/lib.rs"], &[&next])?;
+
+        let mut files = super::changed_files(&repo, "base")?;
+        files.sort_by(|a, b| a.1.cmp(&b.1));
+        assert_eq!(
+            files,
+            vec![
+                (Delta::Added, "changes/12.added.md".into()),
+                (Delta::Added, "src/lib.rs".into()),
+            ]
+        );
+
+        let merge_base = super::merge_base(&repo, "base")?;
+        assert_eq!(merge_base.id(), base.id());
+        let read = |path: &str| super::read_file(&repo, &merge_base, Path::new(path));
+        assert_eq!(read("VERSION")?, Some(String::new()));
+        assert_eq!(read("src/lib.rs")?, None);
+
+        Ok(())
+    }
 }
@@ -6,4 +6,4 @@ pub mod cmd;
 pub mod git;
 pub mod version;
 
-pub use cmd::{GitHubAction, CircleCIEnv, Common, Cmd};
+pub use cmd::{GitHubAction, CircleCIEnv, Common, Cmd, ReleaseOptions};
@@ -1,4 +1,4 @@
-use ::palisade::cmd::{self, Cmd};
+use ::palisade::cmd::{self, Cmd, FragmentsCmd, VersionCmd};
 use structopt::StructOpt;
 
 #[tokio::main]
@@ -7,8 +7,81 @@ async fn main() -> anyhow::Result<()> {
     let cmd = Cmd::from_args();
 
     match cmd {
+        Cmd::Bump {
+            changelog,
+            update_changelog,
+            id,
+            tags,
+            format,
+            version,
+            level,
+        } => {
+            cmd::bump::run(
+                level,
+                id,
+                update_changelog,
+                changelog,
+                tags,
+                format,
+                version,
+            )
+            .await
+        }
+        Cmd::CheckPr {
+            changelog,
+            options,
+            format,
+            version,
+        } => cmd::check_pr::run(changelog, options, format, version).await,
         Cmd::Circle { ccie } => cmd::circleci::run(ccie).await,
-        Cmd::Cut { common, changelog } => cmd::cut::run(common, changelog).await,
+        Cmd::Cut {
+            common,
+            changelog,
+            release,
+        } => cmd::cut::run(common, changelog, release).await,
+        Cmd::Export {
+            changelog,
+            options,
+            format,
+            scheme,
+        } => cmd::export::run(changelog, options, format, scheme.versioning).await,
+        Cmd::Fragments { cmd } => match cmd {
+            FragmentsCmd::Check { fragments, base } => {
+                cmd::fragments_check::run(fragments, base).await
+            }
+        },
         Cmd::GithubAction { gha } => cmd::github_action::run(gha).await,
+        Cmd::Lint {
+            changelog,
+            format,
+            scheme,
+            check_bumps,
+        } => cmd::lint::run(changelog, format, scheme.versioning, check_bumps).await,
+        Cmd::Notes {
+            changelog,
+            write,
+            tags,
+            format,
+            version,
+        } => cmd::notes::run(changelog, write, tags, format, version).await,
+        Cmd::Prepare {
+            changelog,
+            date,
+            fragments,
+            tags,
+            format,
+            options,
+            version,
+        } => cmd::prepare::run(changelog, version, date, fragments, tags, format, options).await,
+        Cmd::Version { cmd } => match cmd {
+            VersionCmd::Check { version } => cmd::version_check::run(version).await,
+        },
+        Cmd::Yank {
+            common,
+            changelog,
+            format,
+            tag,
+            scheme,
+        } => cmd::yank::run(common, changelog, format, tag, scheme.versioning).await,
     }
 }
@@ -0,0 +1,377 @@
+use super::Kind;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::{fs, ops::Range, path::Path};
+
+/// Finds where the version is written in a file of the given kind, returning
+/// the byte range of every copy of it that should be rewritten when the
+/// version changes. A leading `v` is left out of the range so that it is kept.
+pub(crate) fn locate(kind: Kind, data: &str, path: &Path) -> Result<Vec<Range<usize>>> {
+    let found = match kind {
+        Kind::Version => data
+            .lines()
+            .zip(line_starts(data))
+            .find(|(line, _)| !line.trim().is_empty())
+            .map(|(line, start)| vec![trimmed(line, start)])
+            .unwrap_or_default(),
+        Kind::Cargo => cargo(data, path)?,
+        Kind::CargoLock => cargo_lock(data, path)?,
+        Kind::Npm => npm(data).into_iter().collect(),
+        Kind::Pyproject => assignments(data, '=')
+            .filter(|a| {
+                a.key == "version" && (a.section == "project" || a.section == "tool.poetry")
+            })
+            .map(|a| a.value)
+            .take(1)
+            .collect(),
+        Kind::SetupCfg => assignments(data, ':')
+            .filter(|a| a.key == "version" && a.section == "metadata")
+            .map(|a| a.value)
+            .take(1)
+            .collect(),
+        Kind::Maven => maven(data)?.into_iter().collect(),
+        Kind::Helm => helm(data).into_iter().collect(),
+        Kind::Go => go(data).into_iter().collect(),
+    };
+
+    if found.is_empty() {
+        return Err(anyhow!(
+            "{}: can't find the version to update",
+            path.display()
+        ));
+    }
+
+    Ok(found
+        .into_iter()
+        .map(|r| match data[r.clone()].strip_prefix('v') {
+            Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => r.start + 1..r.end,
+            _ => r,
+        })
+        .collect())
+}
+
+/// Replaces the text in each of `ranges` with `text`.
+pub(crate) fn replace(data: &str, ranges: &[Range<usize>], text: &str) -> String {
+    let mut ranges = ranges.to_vec();
+    ranges.sort_by_key(|r| r.start);
+
+    let mut result = data.to_string();
+    for range in ranges.into_iter().rev() {
+        result.replace_range(range, text);
+    }
+
+    result
+}
+
+fn line_starts(data: &str) -> impl Iterator<Item = usize> + '_ {
+    std::iter::once(0).chain(data.match_indices('\n').map(|(i, _)| i + 1))
+}
+
+/// The range of `text` (which starts at `start`) without surrounding
+/// whitespace.
+fn trimmed(text: &str, start: usize) -> Range<usize> {
+    let begin = start + (text.len() - text.trim_start().len());
+    begin..begin + text.trim().len()
+}
+
+/// The range of a value without the quotes around it or a trailing comment.
+fn unquoted(text: &str, start: usize) -> Range<usize> {
+    let range = trimmed(text, start);
+    let value = &text[range.start - start..range.end - start];
+
+    match value.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => match value[1..].find(quote) {
+            Some(end) => range.start + 1..range.start + 1 + end,
+            None => range,
+        },
+        _ => match value.find(" #") {
+            Some(end) => trimmed(&value[..end], range.start),
+            None => range,
+        },
+    }
+}
+
+/// Checks if the value at `range` was a quoted string.
+fn quoted(data: &str, range: &Range<usize>) -> bool {
+    range.start > 0 && matches!(data.as_bytes()[range.start - 1], b'"' | b'\'')
+}
+
+/// A `key = value` line in a TOML or INI style file.
+struct Assignment<'a> {
+    /// The name of the table or section the line is in.
+    section: String,
+    /// Counts `[[array]]` tables, so that lines in the same one can be grouped.
+    table: usize,
+    key: &'a str,
+    value: Range<usize>,
+}
+
+/// Walks the `key = value` lines of a TOML or INI style file. INI files also
+/// allow `key: value`, which is what `separator` is for.
+fn assignments(data: &str, separator: char) -> impl Iterator<Item = Assignment<'_>> {
+    let mut section = String::new();
+    let mut table = 0;
+
+    data.lines()
+        .zip(line_starts(data))
+        .filter_map(move |(line, start)| {
+            let trimmed = line.trim();
+            if trimmed.starts_with('[') {
+                section = trimmed.trim_matches(|c| c == '[' || c == ']').trim().into();
+                table += 1;
+                return None;
+            }
+            if trimmed.starts_with('#') || trimmed.starts_with(';') {
+                return None;
+            }
+
+            let split = line.find(['=', separator])?;
+            Some(Assignment {
+                section: section.clone(),
+                table,
+                key: line[..split].trim(),
+                value: unquoted(&line[split + 1..], start + split + 1),
+            })
+        })
+}
+
+/// `version` in `[package]`, or in `[workspace.package]` for workspaces that
+/// set the version of their members.
+fn cargo(data: &str, path: &Path) -> Result<Vec<Range<usize>>> {
+    let mut inherited = false;
+    for a in assignments(data, '=') {
+        match (a.section.as_str(), a.key) {
+            ("package", "version") | ("workspace.package", "version") if quoted(data, &a.value) => {
+                return Ok(vec![a.value])
+            }
+            ("package", "version") | ("package", "version.workspace") => inherited = true,
+            _ => {}
+        }
+    }
+
+    if inherited {
+        return Err(anyhow!(
+            "{}: the version is inherited from the workspace, update the workspace Cargo.toml instead",
+            path.display()
+        ));
+    }
+    Ok(vec![])
+}
+
+#[derive(Deserialize)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+}
+
+#[derive(Deserialize)]
+struct CargoPackage {
+    name: String,
+}
+
+/// The `version` of the `[[package]]` entries for the package in the
+/// `Cargo.toml` next to the lock file. Only packages in the workspace (with no
+/// `source`) are changed, so dependencies with the same name are left alone.
+fn cargo_lock(data: &str, path: &Path) -> Result<Vec<Range<usize>>> {
+    let manifest = path.with_file_name("Cargo.toml");
+    let name = toml::from_str::<CargoManifest>(&fs::read_to_string(&manifest)?)?
+        .package
+        .map(|p| p.name)
+        .ok_or_else(|| {
+            anyhow!(
+                "{}: no [package] to update in {}",
+                manifest.display(),
+                path.display()
+            )
+        })?;
+
+    let entries: Vec<Assignment<'_>> = assignments(data, '=')
+        .filter(|a| a.section == "package")
+        .collect();
+    let in_table =
+        |table: usize, key: &str| entries.iter().find(|a| a.table == table && a.key == key);
+
+    Ok(entries
+        .iter()
+        .filter(|a| a.key == "version")
+        .filter(|a| match in_table(a.table, "name") {
+            Some(n) => data[n.value.clone()] == name,
+            None => false,
+        })
+        .filter(|a| in_table(a.table, "source").is_none())
+        .map(|a| a.value.clone())
+        .collect())
+}
+
+/// The `version` key of the top level object.
+fn npm(data: &str) -> Option<Range<usize>> {
+    let bytes = data.as_bytes();
+    let string_end = |start: usize| {
+        let mut i = start + 1;
+        while i < bytes.len() && bytes[i] != b'"' {
+            i += if bytes[i] == b'\\' { 2 } else { 1 };
+        }
+        i
+    };
+    let skip_whitespace = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+            i += 1;
+        }
+        i
+    };
+
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => depth -= 1,
+            b'"' => {
+                let end = string_end(i);
+                let after = skip_whitespace(end + 1);
+                if depth == 1
+                    && data.get(i + 1..end) == Some("version")
+                    && bytes.get(after) == Some(&b':')
+                {
+                    let value = skip_whitespace(after + 1);
+                    if bytes.get(value) == Some(&b'"') {
+                        return Some(value + 1..string_end(value));
+                    }
+                }
+                i = end;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// The text of the project `<version>`, or of the property it refers to.
+fn maven(data: &str) -> Result<Option<Range<usize>>> {
+    fn child<'a, 'i>(node: roxmltree::Node<'a, 'i>, name: &str) -> Option<roxmltree::Node<'a, 'i>> {
+        node.children()
+            .find(|n| n.is_element() && n.tag_name().name() == name)
+    }
+
+    let doc = roxmltree::Document::parse(data)?;
+    let project = doc.root_element();
+    let version = match child(project, "version").and_then(|v| v.first_child()) {
+        Some(version) => version,
+        None => return Ok(None),
+    };
+
+    let text = version.text().unwrap_or("").trim();
+    let node = match text.strip_prefix("${").and_then(|v| v.strip_suffix('}')) {
+        Some(property) => child(project, "properties")
+            .and_then(|p| child(p, property))
+            .and_then(|p| p.first_child()),
+        None => Some(version),
+    };
+
+    Ok(node.map(|n| {
+        let range = n.range();
+        trimmed(&data[range.clone()], range.start)
+    }))
+}
+
+/// The top level `version:` of a chart (not `appVersion`).
+fn helm(data: &str) -> Option<Range<usize>> {
+    data.lines()
+        .zip(line_starts(data))
+        .find_map(|(line, start)| {
+            let value = line.strip_prefix("version:")?;
+            Some(unquoted(value, start + "version:".len()))
+        })
+}
+
+/// A Go constant or variable named `Version` set to a string literal, such as
+/// `const Version = "1.2.3"`.
+fn go(data: &str) -> Option<Range<usize>> {
+    data.lines()
+        .zip(line_starts(data))
+        .find_map(|(line, start)| {
+            let decl = line.trim_start();
+            let decl = decl
+                .strip_prefix("const ")
+                .or_else(|| decl.strip_prefix("var "))
+                .unwrap_or(decl)
+                .trim_start();
+            let rest = decl.strip_prefix("Version")?.trim_start();
+            let rest = rest.strip_prefix("string").unwrap_or(rest).trim_start();
+            let value = rest.strip_prefix('=')?;
+            if !value.trim_start().starts_with('"') {
+                return None;
+            }
+
+            Some(unquoted(value, start + (line.len() - value.len())))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::Kind;
+    use std::{fs::read_to_string, path::Path};
+
+    #[test]
+    fn locate() {
+        let files = vec![
+            (Kind::Version, "testdata/VERSION", "0.1.0"),
+            (Kind::Cargo, "testdata/manifests/Cargo.toml", "1.2.3"),
+            (
+                Kind::Cargo,
+                "testdata/manifests/workspace/Cargo.toml",
+                "2.0.0-rc.1",
+            ),
+            (Kind::CargoLock, "testdata/manifests/Cargo.lock", "1.2.3"),
+            (Kind::Npm, "testdata/manifests/package.json", "1.2.3"),
+            (
+                Kind::Pyproject,
+                "testdata/manifests/pyproject.toml",
+                "1.2.3",
+            ),
+            (
+                Kind::Pyproject,
+                "testdata/manifests/poetry/pyproject.toml",
+                "1.2.3",
+            ),
+            (Kind::SetupCfg, "testdata/manifests/setup.cfg", "1.2.3"),
+            (Kind::Maven, "testdata/manifests/pom.xml", "1.2.3"),
+            (Kind::Helm, "testdata/manifests/Chart.yaml", "1.2.3"),
+            (Kind::Go, "testdata/manifests/version.go", "1.2.3"),
+        ];
+
+        for (kind, path, expected) in files {
+            let data = read_to_string(path).unwrap();
+            let ranges = super::locate(kind, &data, Path::new(path)).unwrap();
+            assert_eq!(ranges.len(), 1, "{}", path);
+            assert_eq!(&data[ranges[0].clone()], expected, "{}", path);
+
+            let updated = super::replace(&data, &ranges, "9.9.9");
+            assert_eq!(
+                updated.replace("9.9.9", expected),
+                data,
+                "{} should only change the version",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let path = Path::new("testdata/manifests/workspace/member/Cargo.toml");
+        let data = read_to_string(path).unwrap();
+        let err = super::locate(Kind::Cargo, &data, path).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "testdata/manifests/workspace/member/Cargo.toml: the version is inherited from the workspace, update the workspace Cargo.toml instead"
+        );
+
+        let err =
+            super::locate(Kind::Go, "package version\n", Path::new("version.go")).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "version.go: can't find the version to update"
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::{anyhow, Result};
+use semver::{Prerelease, Version};
+use std::{fmt, fs, path::PathBuf, str::FromStr};
+
+mod edit;
+mod scheme;
+mod source;
+pub use scheme::{CalVer, Scheme};
+pub use source::{Kind, Source};
+
+/// Parses the VERSION file into a version. See
+/// [parse_version](fn.parse_version.html) for what is accepted.
+pub(crate) fn read_version<T>(fname: T, scheme: &Scheme) -> Result<Version> where T: Into<PathBuf> {
+    let fname = fname.into();
+    let version = fs::read_to_string(&fname)?;
+    parse_version(&version, &fname.display().to_string(), scheme)
+}
+
+/// Parses the contents of a VERSION file into a version in the given scheme.
+/// The file must contain exactly one version (an optional leading `v` is
+/// ignored), blank lines and surrounding whitespace are fine. Errors point at
+/// the offending line, using `source` as the file name.
+pub(crate) fn parse_version(data: &str, source: &str, scheme: &Scheme) -> Result<Version> {
+    let mut lines = data
+        .lines()
+        .enumerate()
+        .map(|(n, line)| (n + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let (n, line) = lines
+        .next()
+        .ok_or_else(|| anyhow!("{}: no version found, the file is empty", source))?;
+    if let Some((extra, text)) = lines.next() {
+        return Err(anyhow!(
+            "{}:{}: unexpected {:?}, the file must only contain the version on line {}",
+            source,
+            extra,
+            text,
+            n
+        ));
+    }
+
+    scheme
+        .parse(line)
+        .map_err(|why| anyhow!("{}:{}: {}", source, n, why))
+}
+
+/// How much of a version to increment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Level {
+    Major,
+    Minor,
+    Patch,
+    Premajor,
+    Preminor,
+    Prepatch,
+    Prerelease,
+}
+
+impl FromStr for Level {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "major" => Ok(Level::Major),
+            "minor" => Ok(Level::Minor),
+            "patch" => Ok(Level::Patch),
+            "premajor" => Ok(Level::Premajor),
+            "preminor" => Ok(Level::Preminor),
+            "prepatch" => Ok(Level::Prepatch),
+            "prerelease" => Ok(Level::Prerelease),
+            _ => Err(anyhow!(
+                "unknown bump level {:?}, expected major, minor, patch, premajor, preminor, prepatch or prerelease",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Major => "major",
+            Level::Minor => "minor",
+            Level::Patch => "patch",
+            Level::Premajor => "premajor",
+            Level::Preminor => "preminor",
+            Level::Prepatch => "prepatch",
+            Level::Prerelease => "prerelease",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Checks that going from `previous` to `next` is at least as big a release
+/// as `required`, such as a major release for removed features. `reason` says
+/// why `required` is needed, for the error message, see
+/// [ReleaseEntry::change_level](../changelog/struct.ReleaseEntry.html#method.change_level).
+///
+/// Versions below 1.0.0 follow the Cargo convention of treating the minor
+/// version as the major one, so a `0.3.0` release is enough for breaking
+/// changes since `0.2.5` and a `0.2.6` release is enough for new features.
+///
+/// A prerelease counts as the start of the release it leads up to, so going
+/// from `2.0.0-rc.1` to `2.0.0` (or `2.0.1`) is a major release and from
+/// `1.3.0-rc.1` to `1.3.0` is a minor one.
+pub fn check_bump(previous: &Version, next: &Version, required: Level, reason: &str) -> Result<()> {
+    let rank = |level: Level| match level {
+        Level::Major | Level::Premajor => 3,
+        Level::Minor | Level::Preminor => 2,
+        _ => 1,
+    };
+    let required = match (previous.major, required) {
+        (0, Level::Major) => Level::Minor,
+        (0, _) => Level::Patch,
+        (_, level) => level,
+    };
+    let mut actual = if next.major != previous.major {
+        Level::Major
+    } else if next.minor != previous.minor {
+        Level::Minor
+    } else {
+        Level::Patch
+    };
+    // a prerelease already started its release (`2.0.0-rc.1` is on the way
+    // to a major release), so finishing it counts as that release
+    if !previous.pre.is_empty() && next.major == previous.major {
+        let started = match (previous.minor, previous.patch) {
+            (0, 0) => Level::Major,
+            (_, 0) => Level::Minor,
+            _ => Level::Patch,
+        };
+        if (started == Level::Major || next.minor == previous.minor)
+            && rank(started) > rank(actual)
+        {
+            actual = started;
+        }
+    }
+
+    if rank(actual) >= rank(required) {
+        return Ok(());
+    }
+
+    let name = |level: Level| match rank(level) {
+        3 => "major",
+        2 => "minor",
+        _ => "patch",
+    };
+    Err(anyhow!(
+        "version {} is a {} release after {}, but its changelog entry needs a {} release ({})",
+        next,
+        name(actual),
+        previous,
+        name(required),
+        reason
+    ))
+}
+
+/// Returns the version after `version` at the given level, following the same
+/// rules as `npm version`:
+///
+/// - `major`, `minor` and `patch` increment that part of the version, unless
+///   `version` is a prerelease of exactly that release, in which case it is
+///   released (`1.3.0-rc.1` bumped by `minor` is `1.3.0`)
+/// - `premajor`, `preminor` and `prepatch` increment that part of the version
+///   and start a prerelease `{id}.0` (or just `0` without an id)
+/// - `prerelease` increments the last number of the prerelease
+///   (`2.0.0-rc.1` becomes `2.0.0-rc.2`), starts over at `{id}.0` if the id
+///   changed, and acts like `prepatch` if `version` is not a prerelease
+///
+/// Build metadata is always dropped.
+pub fn bump(version: &Version, level: Level, id: Option<&str>) -> Result<Version> {
+    let (major, minor, patch) = (version.major, version.minor, version.patch);
+    let is_pre = !version.pre.is_empty();
+    let start = match id {
+        Some(id) => format!("{}.0", id),
+        None => "0".into(),
+    };
+
+    let (mut result, pre) = match level {
+        Level::Major if is_pre && minor == 0 && patch == 0 => (Version::new(major, 0, 0), None),
+        Level::Major => (Version::new(major + 1, 0, 0), None),
+        Level::Minor if is_pre && patch == 0 => (Version::new(major, minor, 0), None),
+        Level::Minor => (Version::new(major, minor + 1, 0), None),
+        Level::Patch if is_pre => (Version::new(major, minor, patch), None),
+        Level::Patch => (Version::new(major, minor, patch + 1), None),
+        Level::Premajor => (Version::new(major + 1, 0, 0), Some(start)),
+        Level::Preminor => (Version::new(major, minor + 1, 0), Some(start)),
+        Level::Prepatch => (Version::new(major, minor, patch + 1), Some(start)),
+        Level::Prerelease if !is_pre => (Version::new(major, minor, patch + 1), Some(start)),
+        Level::Prerelease => {
+            let current = version.pre.as_str();
+            let same_id = match id {
+                Some(id) => current.split('.').next() == Some(id),
+                None => true,
+            };
+            let next = if !same_id {
+                start
+            } else {
+                let mut parts: Vec<String> = current.split('.').map(String::from).collect();
+                match parts.iter().rposition(|p| p.parse::<u64>().is_ok()) {
+                    Some(i) => parts[i] = (parts[i].parse::<u64>()? + 1).to_string(),
+                    None => parts.push("0".into()),
+                }
+                parts.join(".")
+            };
+            (Version::new(major, minor, patch), Some(next))
+        }
+    };
+
+    if let Some(pre) = pre {
+        result.pre = Prerelease::new(&pre)
+            .map_err(|why| anyhow!("{:?} is not a valid prerelease: {}", pre, why))?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Level;
+    use semver::Version;
+
+    #[test]
+    fn read_version() {
+        let version = super::read_version("./testdata/VERSION", &Default::default()).unwrap();
+        assert_eq!(version, Version::new(0, 1, 0));
+    }
+
+    #[test]
+    fn parse_version() {
+        let version =
+            super::parse_version("\n  v1.2.3-rc.1\n\n", "VERSION", &Default::default()).unwrap();
+        assert_eq!(version, Version::parse("1.2.3-rc.1").unwrap());
+
+        let errors = vec![
+            ("", "VERSION: no version found, the file is empty"),
+            ("1.2\n", "VERSION:1: \"1.2\" is not a valid semantic version: unexpected end of input while parsing minor version number"),
+            ("\nbanana\n", "VERSION:2: \"banana\" is not a valid semantic version: unexpected character 'b' while parsing major version number"),
+            ("1.2.0\n1.3.0\n", "VERSION:2: unexpected \"1.3.0\", the file must only contain the version on line 1"),
+        ];
+        for (data, message) in errors {
+            let err = super::parse_version(data, "VERSION", &Default::default()).unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+    }
+
+    #[test]
+    fn check_bump() {
+        let v = |v: &str| Version::parse(v).unwrap();
+        assert!(super::check_bump(&v("1.2.3"), &v("2.0.0"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("1.2.3"), &v("1.3.0"), Level::Minor, "").is_ok());
+        assert!(super::check_bump(&v("1.2.3"), &v("1.3.0"), Level::Patch, "").is_ok());
+        assert!(super::check_bump(&v("1.2.3"), &v("2.0.0-rc.1"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("0.2.5"), &v("0.3.0"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("0.2.5"), &v("0.2.6"), Level::Minor, "").is_ok());
+        assert!(super::check_bump(&v("0.2.5"), &v("0.2.6"), Level::Major, "").is_err());
+        assert!(super::check_bump(&v("2.0.0-rc.1"), &v("2.0.0"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("2.0.0-rc.1"), &v("2.0.0-rc.2"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("2.0.0-rc.1"), &v("2.1.0"), Level::Major, "").is_ok());
+        assert!(super::check_bump(&v("1.3.0-rc.1"), &v("1.3.0"), Level::Minor, "").is_ok());
+        assert!(super::check_bump(&v("1.3.0-rc.1"), &v("1.3.0"), Level::Major, "").is_err());
+        assert!(super::check_bump(&v("1.2.4-rc.1"), &v("1.2.4"), Level::Minor, "").is_err());
+
+        let err = super::check_bump(
+            &v("1.2.3"),
+            &v("1.3.0"),
+            Level::Major,
+            "changes under Removed",
+        );
+        assert_eq!(
+            err.unwrap_err().to_string(),
+            "version 1.3.0 is a minor release after 1.2.3, but its changelog entry needs a major release (changes under Removed)"
+        );
+    }
+
+    #[test]
+    fn bump() {
+        let cases = vec![
+            ("1.2.3", Level::Major, None, "2.0.0"),
+            ("1.2.3", Level::Minor, None, "1.3.0"),
+            ("1.2.3", Level::Patch, None, "1.2.4"),
+            ("1.2.3-rc.1", Level::Major, None, "2.0.0"),
+            ("1.2.3-rc.1", Level::Minor, None, "1.3.0"),
+            ("1.2.3-rc.1", Level::Patch, None, "1.2.3"),
+            ("2.0.0-rc.1", Level::Major, None, "2.0.0"),
+            ("1.3.0-rc.1", Level::Minor, None, "1.3.0"),
+            ("1.2.3+build.5", Level::Patch, None, "1.2.4"),
+            ("1.2.3", Level::Premajor, Some("rc"), "2.0.0-rc.0"),
+            ("1.2.3", Level::Preminor, Some("beta"), "1.3.0-beta.0"),
+            ("1.2.3", Level::Prepatch, None, "1.2.4-0"),
+            ("1.2.3", Level::Prerelease, Some("rc"), "1.2.4-rc.0"),
+            ("2.0.0-rc.1", Level::Prerelease, Some("rc"), "2.0.0-rc.2"),
+            ("2.0.0-rc.1", Level::Prerelease, None, "2.0.0-rc.2"),
+            ("2.0.0-beta.3", Level::Prerelease, Some("rc"), "2.0.0-rc.0"),
+            ("2.0.0-rc", Level::Prerelease, None, "2.0.0-rc.0"),
+        ];
+        for (version, level, id, expected) in cases {
+            let version = Version::parse(version).unwrap();
+            let next = super::bump(&version, level, id).unwrap();
+            assert_eq!(
+                next.to_string(),
+                expected,
+                "{} {:?} {:?}",
+                version,
+                level,
+                id
+            );
+        }
+
+        let version = Version::new(1, 2, 3);
+        assert!(super::bump(&version, Level::Premajor, Some("r c")).is_err());
+        assert!("huge".parse::<Level>().is_err());
+    }
+}
@@ -0,0 +1,361 @@
+use super::{bump, Level};
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, NaiveDate};
+use semver::{BuildMetadata, Prerelease, Version};
+use std::{fmt, str::FromStr};
+
+/// How versions are written and incremented.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Scheme {
+    /// [Semantic versioning](https://semver.org), such as `1.2.3`
+    #[default]
+    SemVer,
+    /// [Calendar versioning](https://calver.org), such as `2024.05.1`
+    CalVer(CalVer),
+}
+
+/// A calendar versioning format made of a year, a month and a counter for the
+/// releases in that month, such as `YYYY.0M.MICRO` or `YY.MM.MICRO`.
+///
+/// Calendar versions are stored as semantic versions with the year as the
+/// major version, the month as the minor version and the counter as the patch
+/// version, so they sort the same way. Prerelease and build metadata are
+/// allowed after the counter like they are in semantic versions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CalVer {
+    year: Year,
+    padded_month: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Year {
+    /// `YYYY`, such as 2024
+    Full,
+    /// `YY`, years since 2000 such as 24 (or 6 for 2006)
+    Short,
+    /// `0Y`, zero-padded years since 2000 such as 24 (or 06 for 2006)
+    Padded,
+}
+
+impl CalVer {
+    /// The version for the first release in the month of `date`.
+    fn start(&self, date: NaiveDate) -> Version {
+        let year = match self.year {
+            Year::Full => date.year(),
+            Year::Short | Year::Padded => date.year() - 2000,
+        };
+        Version::new(year as u64, date.month() as u64, 0)
+    }
+}
+
+impl fmt::Display for CalVer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let year = match self.year {
+            Year::Full => "YYYY",
+            Year::Short => "YY",
+            Year::Padded => "0Y",
+        };
+        let month = if self.padded_month { "0M" } else { "MM" };
+        write!(f, "{}.{}.MICRO", year, month)
+    }
+}
+
+impl Scheme {
+    /// Parses a version written in this scheme. A leading `v` is ignored.
+    pub fn parse(&self, text: &str) -> Result<Version> {
+        let version = text.strip_prefix('v').unwrap_or(text);
+        let calver = match self {
+            Scheme::SemVer => {
+                return Version::parse(version)
+                    .map_err(|why| anyhow!("{:?} is not a valid semantic version: {}", text, why))
+            }
+            Scheme::CalVer(calver) => calver,
+        };
+        let invalid = |why: &str| anyhow!("{:?} is not a valid {} version: {}", text, calver, why);
+
+        let (core, build) = match version.find('+') {
+            Some(i) => (&version[..i], &version[i + 1..]),
+            None => (version, ""),
+        };
+        let (core, pre) = match core.find('-') {
+            Some(i) => (&core[..i], &core[i + 1..]),
+            None => (core, ""),
+        };
+
+        let parts: Vec<&str> = core.split('.').collect();
+        if parts.len() != 3 {
+            return Err(invalid("expected three numbers separated by dots"));
+        }
+        if parts
+            .iter()
+            .any(|p| p.is_empty() || !p.bytes().all(|b| b.is_ascii_digit()))
+        {
+            return Err(invalid("the year, month and micro version must be numbers"));
+        }
+        let unpadded = |p: &str| p.len() == 1 || !p.starts_with('0');
+
+        let year = parts[0];
+        let year_ok = match calver.year {
+            Year::Full => year.len() == 4,
+            Year::Short => year.len() <= 2 && unpadded(year),
+            Year::Padded => year.len() == 2,
+        };
+        if !year_ok {
+            return Err(invalid(&format!(
+                "the year {:?} doesn't match the format",
+                year
+            )));
+        }
+
+        let month = parts[1];
+        let month_ok = match calver.padded_month {
+            true => month.len() == 2,
+            false => unpadded(month),
+        };
+        if !month_ok || !(1..=12).contains(&month.parse::<u64>()?) {
+            return Err(invalid(&format!(
+                "the month {:?} doesn't match the format",
+                month
+            )));
+        }
+
+        if !unpadded(parts[2]) {
+            return Err(invalid("the micro version can't have leading zeros"));
+        }
+
+        let mut result = Version::new(year.parse()?, month.parse()?, parts[2].parse()?);
+        result.pre = Prerelease::new(pre).map_err(|why| invalid(&why.to_string()))?;
+        result.build = BuildMetadata::new(build).map_err(|why| invalid(&why.to_string()))?;
+        Ok(result)
+    }
+
+    /// Writes a version the way this scheme does.
+    pub fn format(&self, version: &Version) -> String {
+        let calver = match self {
+            Scheme::SemVer => return version.to_string(),
+            Scheme::CalVer(calver) => calver,
+        };
+
+        let mut result = match calver.year {
+            Year::Padded => format!("{:02}", version.major),
+            Year::Full | Year::Short => version.major.to_string(),
+        };
+        match calver.padded_month {
+            true => result.push_str(&format!(".{:02}", version.minor)),
+            false => result.push_str(&format!(".{}", version.minor)),
+        }
+        result.push_str(&format!(".{}", version.patch));
+        if !version.pre.is_empty() {
+            result.push_str(&format!("-{}", version.pre));
+        }
+        if !version.build.is_empty() {
+            result.push_str(&format!("+{}", version.build));
+        }
+        result
+    }
+
+    /// Returns the version after `version`. Semantic versions are bumped by
+    /// `level`, see [bump](fn.bump.html).
+    ///
+    /// Calendar versions move to the month of `today`: the micro version is
+    /// incremented if `version` is already in this month (or a later one, so
+    /// a clock that is behind never goes back a month), and starts over at 0
+    /// if it is from an earlier month. `major`, `minor` and `patch` all do
+    /// this. The prerelease levels start (or with `prerelease`, continue) a
+    /// prerelease of that version the same way they do for semantic versions.
+    pub fn bump(
+        &self,
+        version: &Version,
+        level: Level,
+        id: Option<&str>,
+        today: NaiveDate,
+    ) -> Result<Version> {
+        let calver = match self {
+            Scheme::SemVer => return bump(version, level, id),
+            Scheme::CalVer(calver) => calver,
+        };
+
+        let start = calver.start(today);
+        let current = (version.major, version.minor) >= (start.major, start.minor);
+        let is_pre = !version.pre.is_empty();
+
+        if current && is_pre {
+            // this month's release is already in progress, so finish it or
+            // continue its prerelease
+            let level = match level {
+                Level::Major | Level::Minor | Level::Patch => Level::Patch,
+                Level::Prerelease => Level::Prerelease,
+                Level::Premajor | Level::Preminor | Level::Prepatch => Level::Prepatch,
+            };
+            return bump(version, level, id);
+        }
+
+        let next = match current {
+            true => Version::new(version.major, version.minor, version.patch + 1),
+            false => start,
+        };
+        match level {
+            Level::Major | Level::Minor | Level::Patch => Ok(next),
+            _ => {
+                let pre = match id {
+                    Some(id) => format!("{}.0", id),
+                    None => "0".into(),
+                };
+                let mut result = next;
+                result.pre = Prerelease::new(&pre)
+                    .map_err(|why| anyhow!("{:?} is not a valid prerelease: {}", pre, why))?;
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Parses `semver` or a calendar versioning format, see [CalVer](struct.CalVer.html).
+impl FromStr for Scheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "semver" {
+            return Ok(Scheme::SemVer);
+        }
+
+        let parts: Vec<&str> = s.split('.').collect();
+        let year = match parts.first() {
+            Some(&"YYYY") => Some(Year::Full),
+            Some(&"YY") => Some(Year::Short),
+            Some(&"0Y") => Some(Year::Padded),
+            _ => None,
+        };
+        let padded_month = match parts.get(1) {
+            Some(&"MM") => Some(false),
+            Some(&"0M") => Some(true),
+            _ => None,
+        };
+
+        match (year, padded_month, parts.get(2), parts.len()) {
+            (Some(year), Some(padded_month), Some(&"MICRO"), 3) => {
+                Ok(Scheme::CalVer(CalVer { year, padded_month }))
+            }
+            _ => Err(anyhow!(
+                "unknown versioning scheme {:?}, expected semver or a calendar version format like YYYY.0M.MICRO (the year can be YYYY, YY or 0Y and the month MM or 0M)",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheme::SemVer => write!(f, "semver"),
+            Scheme::CalVer(calver) => write!(f, "{}", calver),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scheme;
+    use crate::version::Level;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parse() {
+        let cases = vec![
+            ("semver", "1.2.3-rc.1", "1.2.3-rc.1"),
+            ("YYYY.0M.MICRO", "2024.05.1", "2024.5.1"),
+            ("YYYY.MM.MICRO", "v2024.5.0", "2024.5.0"),
+            ("YY.0M.MICRO", "24.05.0-rc.1", "24.5.0-rc.1"),
+            ("YY.MM.MICRO", "6.12.3", "6.12.3"),
+            ("0Y.MM.MICRO", "06.1.0", "6.1.0"),
+        ];
+        for (scheme, text, expected) in cases {
+            let scheme: Scheme = scheme.parse().unwrap();
+            let version = scheme.parse(text).unwrap();
+            assert_eq!(version.to_string(), expected);
+            assert_eq!(scheme.format(&version), text.trim_start_matches('v'));
+        }
+
+        let errors = vec![
+            ("YYYY.0M.MICRO", "2024.5.1", "\"2024.5.1\" is not a valid YYYY.0M.MICRO version: the month \"5\" doesn't match the format"),
+            ("YYYY.0M.MICRO", "2024.13.0", "\"2024.13.0\" is not a valid YYYY.0M.MICRO version: the month \"13\" doesn't match the format"),
+            ("YYYY.0M.MICRO", "24.05.0", "\"24.05.0\" is not a valid YYYY.0M.MICRO version: the year \"24\" doesn't match the format"),
+            ("YY.0M.MICRO", "24.05", "\"24.05\" is not a valid YY.0M.MICRO version: expected three numbers separated by dots"),
+            ("YY.0M.MICRO", "24.05.01", "\"24.05.01\" is not a valid YY.0M.MICRO version: the micro version can't have leading zeros"),
+        ];
+        for (scheme, text, message) in errors {
+            let scheme: Scheme = scheme.parse().unwrap();
+            assert_eq!(scheme.parse(text).unwrap_err().to_string(), message);
+        }
+
+        assert!("YYYY.DD".parse::<Scheme>().is_err());
+        assert!("YYYY.0M.MICRO.MICRO".parse::<Scheme>().is_err());
+    }
+
+    #[test]
+    fn bump() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 20).unwrap();
+        let cases = vec![
+            (
+                "YYYY.0M.MICRO",
+                "2024.04.3",
+                Level::Patch,
+                None,
+                "2024.05.0",
+            ),
+            (
+                "YYYY.0M.MICRO",
+                "2024.05.0",
+                Level::Minor,
+                None,
+                "2024.05.1",
+            ),
+            (
+                "YYYY.0M.MICRO",
+                "2023.05.2",
+                Level::Major,
+                None,
+                "2024.05.0",
+            ),
+            ("YY.0M.MICRO", "24.04.3", Level::Patch, None, "24.05.0"),
+            (
+                "YY.0M.MICRO",
+                "24.05.3",
+                Level::Prerelease,
+                Some("rc"),
+                "24.05.4-rc.0",
+            ),
+            (
+                "YY.0M.MICRO",
+                "24.05.4-rc.0",
+                Level::Prerelease,
+                Some("rc"),
+                "24.05.4-rc.1",
+            ),
+            ("YY.0M.MICRO", "24.05.4-rc.1", Level::Patch, None, "24.05.4"),
+            ("YY.0M.MICRO", "24.04.4-rc.1", Level::Patch, None, "24.05.0"),
+            (
+                "YYYY.0M.MICRO",
+                "2024.06.0",
+                Level::Patch,
+                None,
+                "2024.06.1",
+            ),
+            (
+                "YYYY.0M.MICRO",
+                "2024.06.0-rc.0",
+                Level::Patch,
+                None,
+                "2024.06.0",
+            ),
+            ("semver", "1.2.3", Level::Minor, None, "1.3.0"),
+        ];
+
+        for (scheme, version, level, id, expected) in cases {
+            let scheme: Scheme = scheme.parse().unwrap();
+            let version = scheme.parse(version).unwrap();
+            let next = scheme.bump(&version, level, id, today).unwrap();
+            assert_eq!(scheme.format(&next), expected, "{} {:?}", version, level);
+        }
+    }
+}
@@ -0,0 +1,490 @@
+use super::{edit, parse_version, read_version, Scheme};
+use anyhow::{anyhow, Result};
+use semver::Version;
+use serde::Deserialize;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// The kinds of files the version can be read from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Kind {
+    /// A file containing only the version, see [parse_version](../fn.parse_version.html)
+    #[default]
+    Version,
+    /// `package.version` or `workspace.package.version` in `Cargo.toml`
+    Cargo,
+    /// The entry for the package in `Cargo.lock`, only useful as a target
+    CargoLock,
+    /// `version` in `package.json`
+    Npm,
+    /// `project.version` or `tool.poetry.version` in `pyproject.toml`
+    Pyproject,
+    /// `version` in the `[metadata]` section of `setup.cfg`
+    SetupCfg,
+    /// The project `<version>` in `pom.xml`
+    Maven,
+    /// The chart `version` in a Helm `Chart.yaml`
+    Helm,
+    /// A `Version` string constant or variable in a Go file
+    Go,
+}
+
+impl Kind {
+    /// The name of the file this kind of source is usually in.
+    pub fn default_path(self) -> &'static str {
+        match self {
+            Kind::Version => "VERSION",
+            Kind::Cargo => "Cargo.toml",
+            Kind::CargoLock => "Cargo.lock",
+            Kind::Npm => "package.json",
+            Kind::Pyproject => "pyproject.toml",
+            Kind::SetupCfg => "setup.cfg",
+            Kind::Maven => "pom.xml",
+            Kind::Helm => "Chart.yaml",
+            Kind::Go => "version.go",
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "version" => Ok(Kind::Version),
+            "cargo" => Ok(Kind::Cargo),
+            "cargo-lock" => Ok(Kind::CargoLock),
+            "npm" => Ok(Kind::Npm),
+            "pyproject" => Ok(Kind::Pyproject),
+            "setup-cfg" => Ok(Kind::SetupCfg),
+            "maven" => Ok(Kind::Maven),
+            "helm" => Ok(Kind::Helm),
+            "go" => Ok(Kind::Go),
+            _ => Err(anyhow!(
+                "unknown version file kind {:?}, expected version, cargo, cargo-lock, npm, pyproject, setup-cfg, maven, helm or go",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Kind::Version => "version",
+            Kind::Cargo => "cargo",
+            Kind::CargoLock => "cargo-lock",
+            Kind::Npm => "npm",
+            Kind::Pyproject => "pyproject",
+            Kind::SetupCfg => "setup-cfg",
+            Kind::Maven => "maven",
+            Kind::Helm => "helm",
+            Kind::Go => "go",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A file that holds the version of a project. This is either the source of
+/// truth the version is read from, or a target that is kept in sync with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Source {
+    pub kind: Kind,
+    pub path: PathBuf,
+}
+
+impl Source {
+    /// Creates a source of the given kind, using the usual file name for that
+    /// kind if `path` is `None`.
+    pub fn new(kind: Kind, path: Option<PathBuf>) -> Self {
+        Source {
+            kind,
+            path: path.unwrap_or_else(|| kind.default_path().into()),
+        }
+    }
+
+    /// Reads and parses the version from the source.
+    pub fn read(&self, scheme: &Scheme) -> Result<Version> {
+        if self.kind == Kind::Version {
+            return read_version(&self.path, scheme);
+        }
+
+        let data = fs::read_to_string(&self.path)
+            .map_err(|why| anyhow!("can't read {}: {}", self.path.display(), why))?;
+        self.parse_from(&data, scheme, true)
+    }
+
+    /// Parses the version from `data`, the contents of the source file (such
+    /// as an older copy of it from git).
+    pub fn parse(&self, data: &str, scheme: &Scheme) -> Result<Version> {
+        self.parse_from(data, scheme, false)
+    }
+
+    /// Parses the version from `data`, which was read from the source file
+    /// itself if `on_disk` is set.
+    fn parse_from(&self, data: &str, scheme: &Scheme, on_disk: bool) -> Result<Version> {
+        let name = self.path.display().to_string();
+        if self.kind == Kind::Version {
+            return parse_version(data, &name, scheme);
+        }
+
+        let version = match self.kind {
+            Kind::Cargo => cargo(data, &self.path, on_disk)?,
+            Kind::Npm => npm(data)?,
+            Kind::Pyproject => pyproject(data)?,
+            Kind::SetupCfg => setup_cfg(data)?,
+            Kind::Maven => maven(data)?,
+            Kind::Helm => helm(data)?,
+            Kind::Version | Kind::CargoLock | Kind::Go => {
+                let ranges = edit::locate(self.kind, data, &self.path)?;
+                data[ranges[0].clone()].to_string()
+            }
+        };
+
+        scheme
+            .parse(version.trim())
+            .map_err(|why| anyhow!("{}: {}", name, why))
+    }
+
+    /// Rewrites the version in the file, leaving everything else as it was.
+    pub fn write(&self, version: &Version, scheme: &Scheme) -> Result<()> {
+        let data = fs::read_to_string(&self.path)
+            .map_err(|why| anyhow!("can't read {}: {}", self.path.display(), why))?;
+        let ranges = edit::locate(self.kind, &data, &self.path)?;
+        fs::write(
+            &self.path,
+            edit::replace(&data, &ranges, &scheme.format(version)),
+        )?;
+        Ok(())
+    }
+}
+
+/// Parses `kind` or `kind:path`, such as `cargo` or `helm:charts/yolo/Chart.yaml`.
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parts = s.splitn(2, ':');
+        let kind = parts.next().unwrap_or("").parse()?;
+        Ok(Source::new(kind, parts.next().map(PathBuf::from)))
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.path.display())
+    }
+}
+
+impl Default for Source {
+    fn default() -> Self {
+        Source::new(Kind::default(), None)
+    }
+}
+
+#[derive(Deserialize)]
+struct CargoManifest {
+    package: Option<CargoPackage>,
+    workspace: Option<CargoWorkspace>,
+}
+
+#[derive(Deserialize)]
+struct CargoPackage {
+    version: Option<CargoVersion>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CargoVersion {
+    Version(String),
+    Inherited { workspace: bool },
+}
+
+#[derive(Deserialize)]
+struct CargoWorkspace {
+    package: Option<CargoWorkspacePackage>,
+}
+
+#[derive(Deserialize)]
+struct CargoWorkspacePackage {
+    version: Option<String>,
+}
+
+/// Reads `package.version` from a Cargo manifest. If the package inherits its
+/// version from the workspace (`version.workspace = true`), or there is no
+/// `[package]` at all, `workspace.package.version` is used from this manifest
+/// or the closest workspace manifest above it. The manifest might not be on
+/// disk (`on_disk` is false for an older copy from git), so its path is only
+/// resolved to find the workspace when it is.
+fn cargo(data: &str, path: &Path, on_disk: bool) -> Result<String> {
+    let manifest: CargoManifest = toml::from_str(data)?;
+
+    match manifest.package.and_then(|p| p.version) {
+        Some(CargoVersion::Version(version)) => return Ok(version),
+        Some(CargoVersion::Inherited { workspace: false }) => {
+            return Err(anyhow!(
+                "{}: package.version.workspace is false",
+                path.display()
+            ))
+        }
+        Some(CargoVersion::Inherited { workspace: true }) | None => {}
+    }
+
+    if let Some(workspace) = manifest.workspace {
+        return workspace_version(workspace, path);
+    }
+
+    let path = if on_disk {
+        fs::canonicalize(path)?
+    } else {
+        env::current_dir()?.join(path)
+    };
+    for parent in path.ancestors().skip(2) {
+        let candidate = parent.join("Cargo.toml");
+        if !candidate.exists() {
+            continue;
+        }
+
+        let manifest: CargoManifest = toml::from_str(&fs::read_to_string(&candidate)?)?;
+        if let Some(workspace) = manifest.workspace {
+            return workspace_version(workspace, &candidate);
+        }
+    }
+
+    Err(anyhow!(
+        "{}: no package.version found and the package is not in a workspace",
+        path.display()
+    ))
+}
+
+fn workspace_version(workspace: CargoWorkspace, path: &Path) -> Result<String> {
+    workspace.package.and_then(|p| p.version).ok_or_else(|| {
+        anyhow!(
+            "{}: no package.version or workspace.package.version found",
+            path.display()
+        )
+    })
+}
+
+#[derive(Deserialize)]
+struct PackageJson {
+    version: Option<String>,
+}
+
+fn npm(data: &str) -> Result<String> {
+    let package: PackageJson = serde_json::from_str(data)?;
+    package
+        .version
+        .ok_or_else(|| anyhow!("package.json has no version"))
+}
+
+#[derive(Deserialize)]
+struct Pyproject {
+    project: Option<PyprojectProject>,
+    tool: Option<PyprojectTool>,
+}
+
+#[derive(Deserialize)]
+struct PyprojectProject {
+    version: Option<String>,
+    #[serde(default)]
+    dynamic: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct PyprojectTool {
+    poetry: Option<PyprojectPoetry>,
+}
+
+#[derive(Deserialize)]
+struct PyprojectPoetry {
+    version: Option<String>,
+}
+
+/// Reads the [PEP 621](https://peps.python.org/pep-0621/) `project.version`,
+/// falling back to Poetry's `tool.poetry.version`.
+fn pyproject(data: &str) -> Result<String> {
+    let pyproject: Pyproject = toml::from_str(data)?;
+
+    if let Some(project) = pyproject.project {
+        if let Some(version) = project.version {
+            return Ok(version);
+        }
+        if project.dynamic.iter().any(|d| d == "version") {
+            return Err(anyhow!(
+                "pyproject.toml: the version is dynamic, read it from the file that sets it instead"
+            ));
+        }
+    }
+
+    pyproject
+        .tool
+        .and_then(|t| t.poetry)
+        .and_then(|p| p.version)
+        .ok_or_else(|| anyhow!("pyproject.toml has no project.version or tool.poetry.version"))
+}
+
+/// Reads `version` from the `[metadata]` section of an INI style `setup.cfg`.
+fn setup_cfg(data: &str) -> Result<String> {
+    let mut section = "";
+    for line in data.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            section = line[1..line.len() - 1].trim();
+            continue;
+        }
+        if section != "metadata" {
+            continue;
+        }
+
+        let mut parts = line.splitn(2, ['=', ':']);
+        let (key, value) = match (parts.next(), parts.next()) {
+            (Some(key), Some(value)) => (key.trim(), value.trim()),
+            _ => continue,
+        };
+        if key != "version" {
+            continue;
+        }
+        if value.starts_with("attr:") || value.starts_with("file:") {
+            return Err(anyhow!(
+                "setup.cfg: the version is read with {:?}, read it from that file instead",
+                value
+            ));
+        }
+        return Ok(value.into());
+    }
+
+    Err(anyhow!(
+        "setup.cfg has no version in its [metadata] section"
+    ))
+}
+
+/// Reads the `<version>` of the project (not of its parent or dependencies),
+/// inheriting it from `<parent>` if it is not set. `${property}` references to
+/// `<properties>` are filled in.
+fn maven(data: &str) -> Result<String> {
+    let doc = roxmltree::Document::parse(data)?;
+    let project = doc.root_element();
+    fn child<'a, 'i>(node: roxmltree::Node<'a, 'i>, name: &str) -> Option<roxmltree::Node<'a, 'i>> {
+        node.children()
+            .find(|n| n.is_element() && n.tag_name().name() == name)
+    }
+
+    let version = child(project, "version")
+        .or_else(|| child(project, "parent").and_then(|p| child(p, "version")))
+        .and_then(|n| n.text())
+        .ok_or_else(|| anyhow!("pom.xml has no project version"))?
+        .trim();
+
+    match version.strip_prefix("${").and_then(|v| v.strip_suffix('}')) {
+        Some(property) => child(project, "properties")
+            .and_then(|p| child(p, property))
+            .and_then(|n| n.text())
+            .map(|v| v.trim().to_string())
+            .ok_or_else(|| {
+                anyhow!(
+                    "pom.xml: the version is {}, but there is no such property",
+                    version
+                )
+            }),
+        None => Ok(version.into()),
+    }
+}
+
+#[derive(Deserialize)]
+struct Chart {
+    version: Option<String>,
+}
+
+fn helm(data: &str) -> Result<String> {
+    let chart: Chart = serde_yaml::from_str(data)?;
+    chart
+        .version
+        .ok_or_else(|| anyhow!("Chart.yaml has no version"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Kind, Source};
+    use semver::Version;
+
+    #[test]
+    fn read() {
+        let sources = vec![
+            (Kind::Version, "testdata/VERSION", "0.1.0"),
+            (Kind::Cargo, "testdata/manifests/Cargo.toml", "1.2.3"),
+            (
+                Kind::Cargo,
+                "testdata/manifests/workspace/Cargo.toml",
+                "2.0.0-rc.1",
+            ),
+            (
+                Kind::Cargo,
+                "testdata/manifests/workspace/member/Cargo.toml",
+                "2.0.0-rc.1",
+            ),
+            (Kind::Npm, "testdata/manifests/package.json", "1.2.3"),
+            (
+                Kind::Pyproject,
+                "testdata/manifests/pyproject.toml",
+                "1.2.3",
+            ),
+            (
+                Kind::Pyproject,
+                "testdata/manifests/poetry/pyproject.toml",
+                "1.2.3",
+            ),
+            (Kind::SetupCfg, "testdata/manifests/setup.cfg", "1.2.3"),
+            (Kind::Maven, "testdata/manifests/pom.xml", "1.2.3"),
+            (Kind::Helm, "testdata/manifests/Chart.yaml", "1.2.3"),
+            (Kind::CargoLock, "testdata/manifests/Cargo.lock", "1.2.3"),
+            (Kind::Go, "testdata/manifests/version.go", "1.2.3"),
+        ];
+
+        for (kind, path, expected) in sources {
+            let version = Source::new(kind, Some(path.into()))
+                .read(&Default::default())
+                .unwrap();
+            assert_eq!(version, Version::parse(expected).unwrap(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let source = Source::new(
+            Kind::Cargo,
+            Some("testdata/manifests/virtual/Cargo.toml".into()),
+        );
+        assert_eq!(
+            source.read(&Default::default()).unwrap_err().to_string(),
+            "testdata/manifests/virtual/Cargo.toml: no package.version or workspace.package.version found"
+        );
+
+        assert_eq!(
+            super::setup_cfg("[metadata]\nversion = attr: yolo.__version__\n")
+                .unwrap_err()
+                .to_string(),
+            "setup.cfg: the version is read with \"attr: yolo.__version__\", read it from that file instead"
+        );
+        assert!("gradle".parse::<Kind>().is_err());
+        assert_eq!(
+            "helm:charts/yolo/Chart.yaml".parse::<Source>().unwrap(),
+            Source::new(Kind::Helm, Some("charts/yolo/Chart.yaml".into()))
+        );
+    }
+
+    #[test]
+    fn parse_moved() {
+        // an older copy of a member manifest from git, which has since moved
+        let source = Source::new(
+            Kind::Cargo,
+            Some("testdata/manifests/workspace/old/Cargo.toml".into()),
+        );
+        let version = source
+            .parse("[package]\nversion.workspace = true\n", &Default::default())
+            .unwrap();
+        assert_eq!(version, Version::parse("2.0.0-rc.1").unwrap());
+    }
+}
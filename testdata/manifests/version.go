@@ -0,0 +1,7 @@
+package version
+
+// Version is the current version of yolo.
+const Version = "1.2.3"
+
+// Name is the name of the program.
+var Name = "yolo"
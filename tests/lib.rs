@@ -107,14 +107,14 @@ async fn cut() -> Result<()> {
         token: token.clone(),
         owner: gh_repo.owner.login.clone(),
         name: gh_repo.name.clone(),
-    }, "CHANGELOG.md".into()).await?;
+    }, "CHANGELOG.md".into(), Default::default()).await?;
 
     // check that the release actually exists
     let release = cli.newest_release(gh_repo.owner.login.clone(), gh_repo.name.clone()).await?;
 
     assert_eq!(
         release.body,
-        "Hi there this is a test\\!\n### ADDED\n  - something\n"
+        "Hi there this is a test!\n### ADDED\n- something\n"
     );
 
     // cleanup